@@ -0,0 +1,103 @@
+//! Candidates per second of the second-block search, with and without
+//! tunnels and early abort.
+//!
+//! Run with `cargo bench --bench tunnels`. A candidate is a block that
+//! satisfies the conditions through `Q_20` and is hashed together with its
+//! partner. Without tunnels each candidate is a fresh random block fixed up
+//! by message modification; with tunnels most candidates are a single
+//! tunnel bit flip away from the previous one. Early abort stops hashing a
+//! pair at the first step that leaves the path.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use lab1::md5::transform;
+use lab1::md5_attack::{self, wang_second_block, wang_second_block_path};
+use lab1::task3::second_block_ivs;
+use lab1::tunnels::TunnelSet;
+use lab1::{conditions::BitConditions, consts};
+
+const DURATION: Duration = Duration::from_secs(5);
+
+/// Hash `block` and its partner with two full compression functions.
+fn full(iv: [u32; 4], block: &[u32; 16]) -> bool {
+    let (iv_p, block_p) = wang_second_block_path().partner(iv, block);
+    let (mut state, mut state_p) = (iv, iv_p);
+    transform(&mut state, block);
+    transform(&mut state_p, &block_p);
+    state == state_p
+}
+
+/// Hash `block` and its partner in lockstep until they leave the path.
+fn early(iv: [u32; 4], block: &[u32; 16]) -> bool {
+    let (iv_p, block_p) = wang_second_block_path().partner(iv, block);
+    md5_attack::evaluate(iv, iv_p, block, &block_p).is_collision()
+}
+
+/// A random block fixed up to satisfy `conditions` through `Q_20`.
+fn base(conditions: &BitConditions, iv: [u32; 4], rng: &fastrand::Rng) -> [u32; 16] {
+    let path = wang_second_block_path();
+    loop {
+        let mut block = [0u32; 16];
+        block.iter_mut().for_each(|word| *word = rng.u32(..));
+        if conditions.follow_round1(path, iv, &mut block)
+            && conditions.modify_round2(path, iv, &mut block)
+        {
+            return block;
+        }
+    }
+}
+
+fn report(name: &str, candidates: u64, elapsed: Duration) {
+    println!(
+        "{:<16} {:>12} candidates in {:.2} s, {:>12.0} per second",
+        name,
+        candidates,
+        elapsed.as_secs_f64(),
+        candidates as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn with_tunnels<F>(name: &str, tunnels: &TunnelSet, iv: [u32; 4], evaluate: F)
+where
+    F: Fn([u32; 4], &[u32; 16]) -> bool,
+{
+    let rng = fastrand::Rng::with_seed(1);
+    let start = Instant::now();
+    let mut candidates = 0u64;
+    while start.elapsed() < DURATION {
+        let mut block = base(&tunnels.conditions, iv, &rng);
+        tunnels.enumerate(iv, &mut block, |block| {
+            black_box(evaluate(iv, block));
+            candidates += 1;
+            candidates.is_multiple_of(1 << 16) && start.elapsed() >= DURATION
+        });
+    }
+    report(name, candidates, start.elapsed());
+}
+
+fn main() {
+    let (iv, _) = second_block_ivs(&consts::m0(), &consts::m0_p()).unwrap();
+    let tunnels = TunnelSet::derive(wang_second_block(), wang_second_block_path(), iv, 20);
+    for tunnel in &tunnels.tunnels {
+        println!(
+            "tunnel Q{:<2} {:>2} bits, preserves Q1..Q{}",
+            tunnel.q,
+            tunnel.strength(),
+            tunnel.preserves
+        );
+    }
+
+    let rng = fastrand::Rng::with_seed(1);
+    let start = Instant::now();
+    let mut candidates = 0u64;
+    while start.elapsed() < DURATION {
+        let block = base(wang_second_block(), iv, &rng);
+        black_box(full(iv, &block));
+        candidates += 1;
+    }
+    report("without tunnels", candidates, start.elapsed());
+
+    with_tunnels("with tunnels", &tunnels, iv, full);
+    with_tunnels("and early abort", &tunnels, iv, early);
+}
@@ -0,0 +1,378 @@
+//! Saved state of a multithreaded search, to resume it after the process
+//! stopped.
+//!
+//! A checkpoint is a text file with one `key value` pair per line:
+//!
+//! ```text
+//! lab1 checkpoint
+//! command collide
+//! command prefix.bin
+//! command out.bin
+//! command out_p.bin
+//! master 0x00000000000004d2
+//! iv 8e2f1a09 c4a5d3b7 61f0e28c 0b9d4f35
+//! attempt-limit 100000
+//! elapsed-ms 61250
+//! attempts 15873
+//! candidates 162534912
+//! unmodified 9120
+//! failures 0 0 779 ...
+//! worker 0x5f1c3a0e8d2b7c46 7940
+//! worker 0x9e0b4c7d13a8f215 7933
+//! ```
+//!
+//! `command` repeats once per argument, and `iv` is the chaining value the
+//! command searched from, if it has one of its own. `failures` holds the
+//! 65 counts of the failure [`Histogram`]. `earlier` lines hold pairs found
+//! by earlier searches of the same command, and `spent` the milliseconds
+//! and attempts those searches took, which count against the limits of
+//! this one. Workers are restarted from the first attempt they had not
+//! finished, so a resumed search makes the same attempts as one that was
+//! never stopped. How far an unfinished attempt had walked its tunnel
+//! candidates is not saved; resuming walks them again from the first.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::progress::Histogram;
+use super::task3::{Found, Limits, Spent, Worker};
+use super::utils::parse_u64;
+
+const HEADER: &str = "lab1 checkpoint";
+
+/// The configuration and progress of a search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Arguments of the command that ran the search, without options.
+    pub command: Vec<String>,
+    pub master: u64,
+    /// Chaining value the command searched from, such as that of the prefix
+    /// of a `collide` command.
+    pub iv: Option<[u32; 4]>,
+    pub limits: Limits,
+    /// CSV file the failure histogram goes to.
+    pub histogram: Option<PathBuf>,
+    /// Time spent searching, over every run so far.
+    pub elapsed: Duration,
+    /// Attempts finished by all workers.
+    pub attempts: u64,
+    pub candidates: u64,
+    /// Attempts that message modification could not bring through `Q_20`.
+    pub unmodified: u64,
+    pub failures: Histogram,
+    /// Pairs found by earlier searches of the same command.
+    pub earlier: Vec<Found>,
+    /// What those earlier searches spent.
+    pub spent: Spent,
+    pub workers: Vec<Worker>,
+}
+
+impl Checkpoint {
+    /// Write the checkpoint to `path`. A file is written next to it first
+    /// and renamed over it, so a process killed while saving leaves the
+    /// previous checkpoint intact.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        fs::write(&partial, self.to_string())?;
+        fs::rename(&partial, path)
+    }
+}
+
+/// Why a checkpoint could not be read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseCheckpointError {
+    /// The text does not start with the checkpoint header.
+    Header,
+    /// A line with an unknown key or a value that does not parse.
+    Line { line: usize },
+    /// A key that every checkpoint has is absent.
+    Missing { key: &'static str },
+}
+
+impl fmt::Display for ParseCheckpointError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseCheckpointError::Header => write!(formatter, "not a checkpoint"),
+            ParseCheckpointError::Line { line } => write!(formatter, "line {}: malformed", line),
+            ParseCheckpointError::Missing { key } => write!(formatter, "no {} line", key),
+        }
+    }
+}
+
+impl std::error::Error for ParseCheckpointError {}
+
+impl fmt::Display for Checkpoint {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(formatter, "{}", HEADER)?;
+        for arg in &self.command {
+            writeln!(formatter, "command {}", arg)?;
+        }
+        writeln!(formatter, "master {:#018x}", self.master)?;
+        if let Some(iv) = self.iv {
+            writeln!(formatter, "iv {:08x} {:08x} {:08x} {:08x}", iv[0], iv[1], iv[2], iv[3])?;
+        }
+        if let Some(timeout) = self.limits.timeout {
+            writeln!(formatter, "timeout-ms {}", timeout.as_millis())?;
+        }
+        if let Some(attempts) = self.limits.attempts {
+            writeln!(formatter, "attempt-limit {}", attempts)?;
+        }
+        if let Some(path) = &self.histogram {
+            writeln!(formatter, "histogram {}", path.display())?;
+        }
+        writeln!(formatter, "elapsed-ms {}", self.elapsed.as_millis())?;
+        writeln!(formatter, "attempts {}", self.attempts)?;
+        writeln!(formatter, "candidates {}", self.candidates)?;
+        writeln!(formatter, "unmodified {}", self.unmodified)?;
+        write!(formatter, "failures")?;
+        for count in self.failures.steps {
+            write!(formatter, " {}", count)?;
+        }
+        writeln!(formatter, " {}", self.failures.end)?;
+        for found in &self.earlier {
+            writeln!(
+                formatter,
+                "earlier {:#018x} {} {} {}",
+                found.seed,
+                found.iteration,
+                Words(&found.block),
+                Words(&found.block_p)
+            )?;
+        }
+        if self.spent != Spent::default() {
+            writeln!(
+                formatter,
+                "spent {} {}",
+                self.spent.elapsed.as_millis(),
+                self.spent.attempts
+            )?;
+        }
+        for worker in &self.workers {
+            writeln!(formatter, "worker {:#018x} {}", worker.seed, worker.next)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Checkpoint {
+    type Err = ParseCheckpointError;
+
+    fn from_str(text: &str) -> Result<Checkpoint, ParseCheckpointError> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(ParseCheckpointError::Header);
+        }
+
+        let mut command = Vec::new();
+        let (mut master, mut elapsed, mut attempts, mut candidates, mut failures) =
+            (None, None, None, None, None);
+        let mut unmodified = None;
+        let mut iv = None;
+        let mut limits = Limits::default();
+        let mut histogram = None;
+        let mut earlier = Vec::new();
+        let mut spent = Spent::default();
+        let mut workers = Vec::new();
+
+        for (i, line) in lines {
+            let malformed = ParseCheckpointError::Line { line: i + 1 };
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let parsed = match key {
+                "command" => {
+                    command.push(value.to_string());
+                    Some(())
+                }
+                "master" => parse_u64(value).map(|value| master = Some(value)),
+                "iv" => parse_iv(value).map(|value| iv = Some(value)),
+                "timeout-ms" => value
+                    .parse()
+                    .ok()
+                    .map(|ms| limits.timeout = Some(Duration::from_millis(ms))),
+                "attempt-limit" => value.parse().ok().map(|n| limits.attempts = Some(n)),
+                "histogram" => {
+                    histogram = Some(PathBuf::from(value));
+                    Some(())
+                }
+                "elapsed-ms" => value
+                    .parse()
+                    .ok()
+                    .map(|ms| elapsed = Some(Duration::from_millis(ms))),
+                "attempts" => value.parse().ok().map(|n| attempts = Some(n)),
+                "candidates" => value.parse().ok().map(|n| candidates = Some(n)),
+                "unmodified" => value.parse().ok().map(|n| unmodified = Some(n)),
+                "failures" => parse_failures(value).map(|value| failures = Some(value)),
+                "earlier" => parse_found(value).map(|found| earlier.push(found)),
+                "spent" => parse_spent(value).map(|value| spent = value),
+                "worker" => parse_worker(value).map(|worker| workers.push(worker)),
+                "" => Some(()),
+                _ => None,
+            };
+            parsed.ok_or(malformed)?;
+        }
+
+        let missing = |key| ParseCheckpointError::Missing { key };
+        if workers.is_empty() {
+            return Err(missing("worker"));
+        }
+        Ok(Checkpoint {
+            command,
+            master: master.ok_or(missing("master"))?,
+            iv,
+            limits,
+            histogram,
+            elapsed: elapsed.ok_or(missing("elapsed-ms"))?,
+            attempts: attempts.ok_or(missing("attempts"))?,
+            candidates: candidates.ok_or(missing("candidates"))?,
+            unmodified: unmodified.ok_or(missing("unmodified"))?,
+            failures: failures.ok_or(missing("failures"))?,
+            earlier,
+            spent,
+            workers,
+        })
+    }
+}
+
+/// A block as 128 hexadecimal digits.
+struct Words<'a>(&'a [u32; 16]);
+
+impl fmt::Display for Words<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for word in self.0 {
+            write!(formatter, "{:08x}", word)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_words(value: &str) -> Option<[u32; 16]> {
+    if value.len() != 128 || !value.is_ascii() {
+        return None;
+    }
+    let mut words = [0u32; 16];
+    for (i, word) in words.iter_mut().enumerate() {
+        *word = u32::from_str_radix(&value[8 * i..8 * i + 8], 16).ok()?;
+    }
+    Some(words)
+}
+
+fn parse_iv(value: &str) -> Option<[u32; 4]> {
+    let mut words = value.split(' ');
+    let mut iv = [0u32; 4];
+    for word in iv.iter_mut() {
+        *word = u32::from_str_radix(words.next()?, 16).ok()?;
+    }
+    words.next().is_none().then_some(iv)
+}
+
+fn parse_failures(value: &str) -> Option<Histogram> {
+    let mut histogram = Histogram::default();
+    let mut counts = value.split(' ');
+    for step in histogram.steps.iter_mut() {
+        *step = counts.next()?.parse().ok()?;
+    }
+    histogram.end = counts.next()?.parse().ok()?;
+    counts.next().is_none().then_some(histogram)
+}
+
+fn parse_found(value: &str) -> Option<Found> {
+    let fields: Vec<&str> = value.split(' ').collect();
+    let [seed, iteration, block, block_p] = fields[..] else {
+        return None;
+    };
+    Some(Found {
+        block: parse_words(block)?,
+        block_p: parse_words(block_p)?,
+        seed: parse_u64(seed)?,
+        iteration: iteration.parse().ok()?,
+    })
+}
+
+fn parse_spent(value: &str) -> Option<Spent> {
+    let (elapsed, attempts) = value.split_once(' ')?;
+    Some(Spent {
+        elapsed: Duration::from_millis(elapsed.parse().ok()?),
+        attempts: attempts.parse().ok()?,
+    })
+}
+
+fn parse_worker(value: &str) -> Option<Worker> {
+    let (seed, next) = value.split_once(' ')?;
+    Some(Worker {
+        seed: parse_u64(seed)?,
+        next: next.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint() -> Checkpoint {
+        let mut failures = Histogram::default();
+        failures.record(9);
+        failures.record(64);
+        Checkpoint {
+            command: vec!["collide".into(), "a prefix.bin".into()],
+            master: 0x4d2,
+            iv: Some([0x8e2f1a09, 0xc4a5d3b7, 0x61f0e28c, 0x0b9d4f35]),
+            limits: Limits {
+                timeout: Some(Duration::from_secs(3600)),
+                attempts: None,
+            },
+            histogram: Some(PathBuf::from("failures.csv")),
+            elapsed: Duration::from_millis(61250),
+            attempts: 15,
+            candidates: 1 << 40,
+            unmodified: 12,
+            failures,
+            earlier: vec![Found {
+                block: [0xdeadbeef; 16],
+                block_p: core::array::from_fn(|i| i as u32),
+                seed: u64::MAX,
+                iteration: 3,
+            }],
+            spent: Spent {
+                elapsed: Duration::from_millis(4000),
+                attempts: 90,
+            },
+            workers: vec![Worker { seed: 7, next: 8 }, Worker { seed: 9, next: 7 }],
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let text = checkpoint().to_string();
+        assert!(text.starts_with("lab1 checkpoint\ncommand collide\ncommand a prefix.bin\n"));
+        assert!(text.ends_with("worker 0x0000000000000007 8\nworker 0x0000000000000009 7\n"));
+        assert_eq!(text.parse(), Ok(checkpoint()));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        let text = checkpoint().to_string();
+        assert_eq!("".parse::<Checkpoint>(), Err(ParseCheckpointError::Header));
+        assert_eq!(
+            text.replace("attempts 15", "attempts many")
+                .parse::<Checkpoint>(),
+            Err(ParseCheckpointError::Line { line: 9 })
+        );
+        assert_eq!(
+            text.replace("failures 0", "failures").parse::<Checkpoint>(),
+            Err(ParseCheckpointError::Line { line: 12 })
+        );
+        let without_workers: String = text
+            .lines()
+            .filter(|line| !line.starts_with("worker"))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        assert_eq!(
+            without_workers.parse::<Checkpoint>(),
+            Err(ParseCheckpointError::Missing { key: "worker" })
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Identical-prefix collisions for arbitrary files.
+//!
+//! The prefix is padded with zero bytes to a whole number of blocks and
+//! hashed to a chaining value. Both collision blocks are searched from it,
+//! so the two outputs
+//!
+//! ```text
+//! prefix || padding || m0  || m1  || suffix
+//! prefix || padding || m0' || m1' || suffix
+//! ```
+//!
+//! have the same MD5 for every common suffix.
+
+use std::fmt;
+
+use super::md5::{transform, words_to_bytes, Context};
+use super::task3::{
+    parallel_find_m0_m0_p, parallel_find_m1_m1_p, Found, SearchConfig, SECOND_BLOCK_MESSAGE_DIFF,
+};
+
+/// `prefix` followed by zero bytes up to a multiple of 64.
+pub fn pad(prefix: &[u8]) -> Vec<u8> {
+    let mut padded = prefix.to_vec();
+    padded.resize(prefix.len().div_ceil(64) * 64, 0);
+    padded
+}
+
+/// Two messages with the same MD5, and the searches that found their
+/// blocks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Collision {
+    pub message: Vec<u8>,
+    pub message_p: Vec<u8>,
+    pub first: Found,
+    pub second: Found,
+}
+
+/// A checkpoint resumed with a prefix other than the one it was saved for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrefixMismatch {
+    /// Chaining value of the prefix given.
+    pub expected: [u32; 4],
+    /// Chaining value saved in the checkpoint, if any.
+    pub saved: Option<[u32; 4]>,
+}
+
+impl fmt::Display for PrefixMismatch {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let hex = |iv: [u32; 4]| format!("{:08x} {:08x} {:08x} {:08x}", iv[0], iv[1], iv[2], iv[3]);
+        match self.saved {
+            Some(saved) => write!(
+                formatter,
+                "the checkpoint was saved for another prefix: it holds chaining value {}, \
+                 the prefix gives {}",
+                hex(saved),
+                hex(self.expected)
+            ),
+            None => write!(
+                formatter,
+                "the checkpoint holds no chaining value, the prefix gives {}",
+                hex(self.expected)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PrefixMismatch {}
+
+/// Find two messages that start with `prefix`, end with `suffix` and have
+/// the same MD5, searching each block as `config` says.
+///
+/// Returns `None` if either search runs out of its limits. The first block
+/// pair alone takes an hour or more on one core; the second takes minutes.
+/// The limits of `config` cover both searches. Checkpoints carry the
+/// chaining value of the prefix, and the first pair as soon as it is found;
+/// resuming from one goes straight on with the second search. Each worker
+/// resumes after the last attempt it finished, so an attempt that was under
+/// way is made again from the start. Fails before searching if
+/// `config.resume` was saved for another prefix.
+pub fn collide(
+    prefix: &[u8],
+    suffix: &[u8],
+    config: &SearchConfig,
+) -> Result<Option<Collision>, PrefixMismatch> {
+    let padded = pad(prefix);
+    let mut context = Context::new();
+    context.consume(&padded);
+    let iv = context.state();
+
+    let resume = config.resume.as_ref();
+    if let Some(checkpoint) = resume {
+        if checkpoint.iv != Some(iv) {
+            return Err(PrefixMismatch {
+                expected: iv,
+                saved: checkpoint.iv,
+            });
+        }
+    }
+    let config = SearchConfig {
+        iv: Some(iv),
+        ..config.clone()
+    };
+    // A checkpoint with the first pair also holds what finding it spent.
+    let (first, spent) = match resume.and_then(|checkpoint| checkpoint.earlier.first()) {
+        Some(first) => (*first, config.spent),
+        None => {
+            let config = SearchConfig {
+                label: Some("first"),
+                ..config.clone()
+            };
+            let search = parallel_find_m0_m0_p(iv, &config);
+            let Some(first) = search.found else {
+                return Ok(None);
+            };
+            (first, search.spent)
+        }
+    };
+    let (mut iv1, mut iv1_p) = (iv, iv);
+    transform(&mut iv1, &first.block);
+    transform(&mut iv1_p, &first.block_p);
+
+    let config = SearchConfig {
+        resume: config
+            .resume
+            .clone()
+            .filter(|checkpoint| !checkpoint.earlier.is_empty()),
+        earlier: vec![first],
+        spent,
+        label: Some("second"),
+        ..config.clone()
+    };
+    let second = parallel_find_m1_m1_p(iv1, iv1_p, &SECOND_BLOCK_MESSAGE_DIFF, &config)
+        .expect("the second-block difference is the one of its path");
+
+    Ok(second.found.map(|second| Collision {
+        message: assemble(&padded, [&first.block, &second.block], suffix),
+        message_p: assemble(&padded, [&first.block_p, &second.block_p], suffix),
+        first,
+        second,
+    }))
+}
+
+/// `padded || blocks || suffix`.
+fn assemble(padded: &[u8], blocks: [&[u32; 16]; 2], suffix: &[u8]) -> Vec<u8> {
+    let mut message = padded.to_vec();
+    for block in blocks {
+        message.extend_from_slice(&words_to_bytes(block));
+    }
+    message.extend_from_slice(suffix);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkpoint::Checkpoint;
+    use crate::md5::bytes_to_words;
+    use crate::{consts, md5};
+
+    #[test]
+    fn pads_to_whole_blocks() {
+        assert_eq!(pad(b"").len(), 0);
+        assert_eq!(pad(b"abc"), [b"abc".as_slice(), &[0; 61]].concat());
+        assert_eq!(pad(&[1; 64]).len(), 64);
+        assert_eq!(pad(&[1; 65]).len(), 128);
+    }
+
+    #[test]
+    fn published_blocks_collide_with_suffix() {
+        let blocks = [consts::m0(), consts::m1(), consts::m0_p(), consts::m1_p()]
+            .map(|block| bytes_to_words(&block));
+        let suffix = b"common suffix";
+        let message = assemble(&pad(b""), [&blocks[0], &blocks[1]], suffix);
+        let message_p = assemble(&pad(b""), [&blocks[2], &blocks[3]], suffix);
+        assert_ne!(message, message_p);
+        assert_eq!(md5::compute(&message), md5::compute(&message_p));
+    }
+
+    #[test]
+    fn refuses_checkpoint_of_another_prefix() {
+        let mut context = Context::new();
+        context.consume(pad(b"another prefix"));
+        let iv = context.state();
+        let text = |iv: &str| {
+            format!(
+                "lab1 checkpoint\nmaster 1\n{}elapsed-ms 0\nattempts 0\ncandidates 0\n\
+                 unmodified 0\nfailures{}\nworker 0x1 0\n",
+                iv,
+                " 0".repeat(65)
+            )
+        };
+        let saved = format!("iv {:08x} {:08x} {:08x} {:08x}\n", iv[0], iv[1], iv[2], iv[3]);
+        for (text, saved) in [(text(&saved), Some(iv)), (text(""), None)] {
+            let config = SearchConfig {
+                resume: Some(text.parse::<Checkpoint>().unwrap()),
+                ..SearchConfig::new(1)
+            };
+            let error = collide(b"prefix", b"", &config).unwrap_err();
+            assert_eq!(error.saved, saved);
+            assert_ne!(error.expected, iv);
+            let message = error.to_string();
+            assert!(message.contains(&format!("{:08x}", error.expected[3])), "{}", message);
+            assert_eq!(message.contains(&format!("{:08x}", iv[3])), saved.is_some(), "{}", message);
+        }
+    }
+
+    #[test]
+    fn resumed_collide_assembles_colliding_messages() {
+        // Seed 1 reaches a second pair after the published first pair at
+        // attempt 90843, so resuming there finds it in one attempt.
+        let first = Found {
+            block: bytes_to_words(&consts::m0()),
+            block_p: bytes_to_words(&consts::m0_p()),
+            seed: 0,
+            iteration: 0,
+        };
+        let iv = Context::new().state();
+        let text = format!(
+            "lab1 checkpoint\nmaster 0x1\niv {:08x} {:08x} {:08x} {:08x}\nattempt-limit 90844\n\
+             elapsed-ms 0\nattempts 90843\ncandidates 0\nunmodified 0\nfailures{}\n\
+             earlier 0x0 0 {} {}\nworker 0x1 90843\n",
+            iv[0],
+            iv[1],
+            iv[2],
+            iv[3],
+            " 0".repeat(65),
+            first.block.map(|word| format!("{:08x}", word)).concat(),
+            first.block_p.map(|word| format!("{:08x}", word)).concat(),
+        );
+        let config = SearchConfig {
+            resume: Some(text.parse::<Checkpoint>().unwrap()),
+            ..SearchConfig::new(1)
+        };
+        let suffix = b"common suffix";
+        let collision = collide(b"", suffix, &config).unwrap().unwrap();
+        assert_eq!(collision.first, first);
+        assert_eq!((collision.second.seed, collision.second.iteration), (1, 90843));
+
+        let (message, message_p) = (&collision.message, &collision.message_p);
+        assert_eq!(message.len(), 128 + suffix.len());
+        assert_eq!(message[..64], consts::m0());
+        assert_eq!(message_p[..64], consts::m0_p());
+        assert_eq!(message[64..128], words_to_bytes(&collision.second.block));
+        assert_eq!(message_p[64..128], words_to_bytes(&collision.second.block_p));
+        assert!(message.ends_with(suffix) && message_p.ends_with(suffix));
+        assert_ne!(message, message_p);
+        assert_eq!(md5::compute(message), md5::compute(message_p));
+    }
+}
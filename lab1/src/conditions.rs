@@ -0,0 +1,721 @@
+//! Sufficient conditions on the working states `Q_t`, in the bit-condition
+//! notation of Stevens.
+//!
+//! A table has one row per step, written from bit 32 down to bit 1 in four
+//! groups of eight:
+//!
+//! ```text
+//! Q1   1...010. ..1..... ....0... ..0.....
+//! Q2   1^^^110. ..0^^^^^ 0..^1... ^^0..00.
+//! ```
+//!
+//! | Symbol | Condition on `Q_t[i]`                        |
+//! |--------|----------------------------------------------|
+//! | `.`    | none                                         |
+//! | `0`    | `Q_t[i] = 0`                                 |
+//! | `1`    | `Q_t[i] = 1`                                 |
+//! | `^`    | `Q_t[i] = Q_{t-1}[i]`                        |
+//! | `!`    | `Q_t[i] != Q_{t-1}[i]`                       |
+//! | `m`    | `Q_t[i] = Q_{t-2}[i]`                        |
+//! | `#`    | `Q_t[i] != Q_{t-2}[i]`                       |
+//! | `+`    | `Q_t[i] = 0` and `Q'_t[i] = 1`               |
+//! | `-`    | `Q_t[i] = 1` and `Q'_t[i] = 0`               |
+
+use core::fmt;
+use core::str::FromStr;
+
+use super::differential::{Bsdr, DifferentialPath};
+use super::md5::{states, step, step_inverse, transform_traced, WORD_INDEX};
+
+/// First step index of a table, `Q_{-3}`.
+pub const FIRST_STEP: isize = -3;
+/// Last step index of a table, `Q_64`.
+pub const LAST_STEP: isize = 64;
+
+const ROWS: usize = (LAST_STEP - FIRST_STEP + 1) as usize;
+
+/// A condition on a single bit of `Q_t`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Condition {
+    #[default]
+    Free,
+    Zero,
+    One,
+    SameAsPrev,
+    NotPrev,
+    SameAsPrev2,
+    NotPrev2,
+    Plus,
+    Minus,
+}
+
+impl Condition {
+    pub fn from_char(symbol: char) -> Option<Condition> {
+        match symbol {
+            '.' => Some(Condition::Free),
+            '0' => Some(Condition::Zero),
+            '1' => Some(Condition::One),
+            '^' => Some(Condition::SameAsPrev),
+            '!' => Some(Condition::NotPrev),
+            'm' => Some(Condition::SameAsPrev2),
+            '#' => Some(Condition::NotPrev2),
+            '+' => Some(Condition::Plus),
+            '-' => Some(Condition::Minus),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Condition::Free => '.',
+            Condition::Zero => '0',
+            Condition::One => '1',
+            Condition::SameAsPrev => '^',
+            Condition::NotPrev => '!',
+            Condition::SameAsPrev2 => 'm',
+            Condition::NotPrev2 => '#',
+            Condition::Plus => '+',
+            Condition::Minus => '-',
+        }
+    }
+}
+
+/// The conditions of one row collapsed into bit masks, as seen by a single
+/// message: `+` counts as `0` and `-` as `1`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RowMasks {
+    pub zero: u32,
+    pub one: u32,
+    pub same: u32,
+    pub flip: u32,
+    pub same2: u32,
+    pub flip2: u32,
+}
+
+impl RowMasks {
+    /// Force `q` to satisfy the masks, given `Q_{t-1}` and `Q_{t-2}`.
+    #[inline]
+    pub fn apply(&self, q: u32, prev: u32, prev2: u32) -> u32 {
+        let q = (q & !self.zero) | self.one;
+        let q = (q & !self.same) | (prev & self.same);
+        let q = (q & !self.flip) | (!prev & self.flip);
+        let q = (q & !self.same2) | (prev2 & self.same2);
+        (q & !self.flip2) | (!prev2 & self.flip2)
+    }
+
+    /// Return the bits of `q` that violate the masks, given `Q_{t-1}` and
+    /// `Q_{t-2}`.
+    #[inline]
+    pub fn violations(&self, q: u32, prev: u32, prev2: u32) -> u32 {
+        (q & self.zero)
+            | (!q & self.one)
+            | ((q ^ prev) & self.same)
+            | (!(q ^ prev) & self.flip)
+            | ((q ^ prev2) & self.same2)
+            | (!(q ^ prev2) & self.flip2)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+
+    /// Number of bits with a condition.
+    #[inline]
+    pub fn count(&self) -> u32 {
+        (self.zero | self.one | self.same | self.flip | self.same2 | self.flip2).count_ones()
+    }
+}
+
+/// A table of bit conditions for `Q_{-3}` to `Q_64`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct BitConditions {
+    rows: [[Condition; 32]; ROWS],
+    masks: [RowMasks; ROWS],
+}
+
+impl Default for BitConditions {
+    fn default() -> Self {
+        BitConditions {
+            rows: [[Condition::Free; 32]; ROWS],
+            masks: [RowMasks::default(); ROWS],
+        }
+    }
+}
+
+impl BitConditions {
+    /// Return the condition on bit `bit` (0-based) of `Q_t`.
+    #[inline]
+    pub fn get(&self, t: isize, bit: usize) -> Condition {
+        self.rows[row_index(t)][bit]
+    }
+
+    /// Set the condition on bit `bit` (0-based) of `Q_t`.
+    #[inline]
+    pub fn set(&mut self, t: isize, bit: usize, condition: Condition) {
+        let index = row_index(t);
+        self.rows[index][bit] = condition;
+        self.masks[index] = row_masks(&self.rows[index]);
+    }
+
+    /// Return the masks of row `t`.
+    #[inline]
+    pub fn masks(&self, t: isize) -> RowMasks {
+        self.masks[row_index(t)]
+    }
+
+    /// Apply single-message modification to the first round of `block`.
+    ///
+    /// Each of `Q_1` to `Q_16` is computed, forced to satisfy its row, and
+    /// the message word is recomputed from the corrected value. Returns
+    /// `Q_{-3}` to `Q_16`, oldest first.
+    pub fn modify_round1(&self, iv: [u32; 4], block: &mut [u32; 16]) -> [u32; 20] {
+        let mut q = [0u32; 20];
+        q[..4].copy_from_slice(&[iv[0], iv[3], iv[2], iv[1]]);
+        for t in 0..16 {
+            // Q_t, Q_{t-1}, Q_{t-2}, Q_{t-3}
+            let window = [q[t + 3], q[t + 2], q[t + 1], q[t]];
+            let next = step(t, window, block[t]);
+            let next = self.masks(t as isize + 1).apply(next, window[0], window[1]);
+            block[t] = step_inverse(t, window, next);
+            q[t + 4] = next;
+        }
+        q
+    }
+
+    /// Like [`modify_round1`](Self::modify_round1), but also keep the pair
+    /// on `path`.
+    ///
+    /// The bit conditions fix the difference of every boolean function, but
+    /// not how the rotation treats the difference of `T_t`, which depends on
+    /// the value of `T_t`. When `Q_{t+1}` deviates from `path`, its free bits
+    /// are flipped one at a time until it does not. Returns whether `Q_1` to
+    /// `Q_16` all follow `path`.
+    pub fn follow_round1(
+        &self,
+        path: &DifferentialPath,
+        iv: [u32; 4],
+        block: &mut [u32; 16],
+    ) -> bool {
+        let (iv_p, _) = path.partner(iv, block);
+        let mut q = [0u32; 20];
+        let mut q_p = [0u32; 20];
+        q[..4].copy_from_slice(&[iv[0], iv[3], iv[2], iv[1]]);
+        q_p[..4].copy_from_slice(&[iv_p[0], iv_p[3], iv_p[2], iv_p[1]]);
+        let mut follows = true;
+        for t in 0..16 {
+            let window = [q[t + 3], q[t + 2], q[t + 1], q[t]];
+            let window_p = [q_p[t + 3], q_p[t + 2], q_p[t + 1], q_p[t]];
+            let masks = self.masks(t as isize + 1);
+            let next = masks.apply(step(t, window, block[t]), window[0], window[1]);
+            let partner_of = |next: u32| {
+                let word = step_inverse(t, window, next);
+                (word, step(t, window_p, word.wrapping_add(path.message[t])))
+            };
+            let deviates = |next: u32, next_p: u32| {
+                path.deviations(t as isize + 1, Bsdr::between(next, next_p)) != 0
+            };
+            let (mut word, mut next_p) = partner_of(next);
+            let mut chosen = next;
+            if deviates(next, next_p) {
+                let flipped = free_bits(&masks)
+                    .map(|flip| next ^ flip)
+                    .map(|next| (next, partner_of(next)))
+                    .find(|&(next, (_, next_p))| !deviates(next, next_p));
+                match flipped {
+                    Some((next, (w, p))) => (chosen, word, next_p) = (next, w, p),
+                    None => follows = false,
+                }
+            }
+            block[t] = word;
+            q[t + 4] = chosen;
+            q_p[t + 4] = next_p;
+        }
+        follows
+    }
+
+    /// Apply Wang's multi-message modification to `a_5`, `d_5`, `c_5` and
+    /// `b_5`, that is `Q_17` to `Q_20`, of `block`, which must already follow
+    /// `path` through the first round.
+    ///
+    /// When `Q_{t+1}` breaks its row, every broken bit is forced at once and
+    /// the change is made in one of three ways, each kept only if `Q_1` to
+    /// `Q_{t+1}` then satisfy the table and follow `path`:
+    ///
+    /// - Through the word `W_w` of step `t`, which also computes `Q_{w+1}`
+    ///   in round 1. This is Wang's correction.
+    /// - Through `Q_{t-3}`, one of `Q_13` to `Q_16`, which is added to `T_t`
+    ///   like `W_w` and takes up the same change. Its word is in round 1
+    ///   only.
+    /// - By flipping a free bit of one of `Q_13` to `Q_t`, whose change
+    ///   reaches `Q_{t+1}` through the boolean functions in between.
+    ///
+    /// Each state `Q_{s+1}` is moved through the word of its step, and the
+    /// following words of round 1 are recomputed so that the states after
+    /// it keep their values. Words already used in round 2 are kept, and the
+    /// states they compute move instead; this only happens for `b_5`, whose
+    /// `W_0` moves `Q_1` and, through `W_1`, `Q_2`. The first two ways are
+    /// also tried with one free bit of `Q_{t+1}` flipped, since the rotation
+    /// can carry the partner's difference off `path` otherwise. Returns
+    /// whether `Q_1` to `Q_20` all satisfy the table and follow `path`.
+    pub fn modify_round2(
+        &self,
+        path: &DifferentialPath,
+        iv: [u32; 4],
+        block: &mut [u32; 16],
+    ) -> bool {
+        let (mut q, mut q_p) = pair_states(path, iv, block);
+        // Words used by the steps of round 2 corrected so far.
+        let mut kept = 0u16;
+        for t in 16..20 {
+            let row = t as isize + 1;
+            let w = WORD_INDEX[t];
+            if self.broken(path, &q, &q_p, row) != 0 {
+                let masks = self.masks(row);
+                let corrected = masks.apply(q[t + 4], q[t + 3], q[t + 2]);
+                let targets = core::iter::once(0)
+                    .chain(free_bits(&masks))
+                    .map(|flip| corrected ^ flip);
+                let window = [q[t + 3], q[t + 2], q[t + 1], q[t]];
+                // Q_{t-3} is added to T_t like the word, so it can take up
+                // the change of the word instead.
+                let additive = targets.clone().map(|target| {
+                    let change = step_inverse(t, window, target).wrapping_sub(block[w]);
+                    (t - 4, q[t].wrapping_add(change), kept | 1 << w)
+                });
+                let own = targets.map(|target| (t, target, kept));
+                // A free bit of Q_13 to Q_t reaches Q_{t+1} through the
+                // boolean functions in between.
+                let earlier = (12..t).rev().flat_map(|s| {
+                    let kept = kept & !(1 << WORD_INDEX[s]) | 1 << w;
+                    free_bits(&self.masks(s as isize + 1))
+                        .map(move |flip| (s, q[s + 4] ^ flip, kept))
+                });
+                let corrected = own
+                    .chain(additive)
+                    .chain(earlier)
+                    .find_map(|(s, target, kept)| {
+                        self.correct(path, iv, block, &q, s, target, kept, row)
+                    });
+                match corrected {
+                    Some(corrected) => (*block, q, q_p) = corrected,
+                    None => return false,
+                }
+            }
+            kept |= 1 << w;
+        }
+        true
+    }
+
+    /// Set `Q_{s+1}` of `block` to `target` through the word of step `s`,
+    /// and move round 1 with [`move_word`]. Returns the new block and the
+    /// states of both messages if `Q_1` to `Q_last` then satisfy the table
+    /// and follow `path`.
+    #[allow(clippy::too_many_arguments)]
+    fn correct(
+        &self,
+        path: &DifferentialPath,
+        iv: [u32; 4],
+        block: &[u32; 16],
+        q: &[u32; 24],
+        s: usize,
+        target: u32,
+        kept: u16,
+        last: isize,
+    ) -> Option<([u32; 16], [u32; 24], [u32; 24])> {
+        let mut trial = *block;
+        let w = WORD_INDEX[s];
+        let word = step_inverse(s, [q[s + 3], q[s + 2], q[s + 1], q[s]], target);
+        let (moved, until) = move_word(q, &mut trial, w, word, kept);
+        // The first message alone rules out most corrections.
+        let broken = (w + 1..=(until + 3).min(16)).any(|k| {
+            let masks = self.masks(k as isize);
+            masks.violations(moved[k + 3], moved[k + 2], moved[k + 1]) != 0
+        });
+        if broken {
+            return None;
+        }
+        let (q, q_p) = pair_states(path, iv, &trial);
+        (1..=last)
+            .all(|k| self.broken(path, &q, &q_p, k) == 0)
+            .then_some((trial, q, q_p))
+    }
+
+    /// Bits of `Q_t` that break its row or deviate from `path`, given the
+    /// round states of both messages.
+    fn broken(&self, path: &DifferentialPath, q: &[u32; 24], q_p: &[u32; 24], t: isize) -> u32 {
+        let index = (t + 3) as usize;
+        let conditions = self.masks(t).violations(q[index], q[index - 1], q[index - 2]);
+        conditions | path.deviations(t, Bsdr::between(q[index], q_p[index]))
+    }
+
+    /// Check `block` from chaining value `iv` against every row of the table
+    /// and against `path`.
+    ///
+    /// The first message is checked against the bit conditions. The second
+    /// message is `block` plus the message difference of `path`, hashed from
+    /// `iv` plus the chaining value difference of `path`; every bit where the
+    /// pair deviates from the path is reported too.
+    pub fn check(
+        &self,
+        path: &DifferentialPath,
+        iv: [u32; 4],
+        block: &[u32; 16],
+    ) -> ConditionReport {
+        let (iv_p, block_p) = path.partner(iv, block);
+        let trace = transform_traced(iv, block);
+        let trace_p = transform_traced(iv_p, &block_p);
+
+        let mut violations = Vec::new();
+        for t in FIRST_STEP..=LAST_STEP {
+            let q = trace.q(t);
+            let prev = if t > FIRST_STEP { trace.q(t - 1) } else { 0 };
+            let prev2 = if t > FIRST_STEP + 1 { trace.q(t - 2) } else { 0 };
+            let broken = self.masks(t).violations(q, prev, prev2);
+            let deviating = path.deviations(t, Bsdr::between(q, trace_p.q(t))) & !broken;
+            for bit in 0..32 {
+                let kind = if broken >> bit & 1 == 1 {
+                    ViolationKind::Condition
+                } else if deviating >> bit & 1 == 1 {
+                    ViolationKind::Difference
+                } else {
+                    continue;
+                };
+                violations.push(Violation {
+                    t,
+                    bit,
+                    kind,
+                    condition: self.get(t, bit),
+                });
+            }
+        }
+        ConditionReport { violations }
+    }
+
+    /// Whether the chaining values `iv` and `iv_p` satisfy the rows of
+    /// `Q_{-3}` to `Q_0` and follow `path` there.
+    ///
+    /// A search for the block before this one has to end in such a pair.
+    pub fn admits_iv(&self, path: &DifferentialPath, iv: [u32; 4], iv_p: [u32; 4]) -> bool {
+        // Q_{-3}, Q_{-2}, Q_{-1}, Q_0 are a, d, c, b.
+        let q = [iv[0], iv[3], iv[2], iv[1]];
+        let q_p = [iv_p[0], iv_p[3], iv_p[2], iv_p[1]];
+        (0..4).all(|i| {
+            let t = FIRST_STEP + i as isize;
+            let prev = if i >= 1 { q[i - 1] } else { 0 };
+            let prev2 = if i >= 2 { q[i - 2] } else { 0 };
+            self.masks(t).violations(q[i], prev, prev2) == 0
+                && path.deviations(t, Bsdr::between(q[i], q_p[i])) == 0
+        })
+    }
+}
+
+/// Bits of a row without a condition.
+fn free(masks: &RowMasks) -> u32 {
+    !(masks.zero | masks.one | masks.same | masks.flip | masks.same2 | masks.flip2)
+}
+
+/// Each bit of a row without a condition, as a mask.
+fn free_bits(masks: &RowMasks) -> impl Iterator<Item = u32> + Clone {
+    let free = free(masks);
+    (0..32).filter(move |bit| free >> bit & 1 == 1).map(|bit| 1 << bit)
+}
+
+fn row_masks(row: &[Condition; 32]) -> RowMasks {
+    let mut masks = RowMasks::default();
+    for (bit, condition) in row.iter().enumerate() {
+        let mask = 1u32 << bit;
+        match condition {
+            Condition::Free => {}
+            Condition::Zero | Condition::Plus => masks.zero |= mask,
+            Condition::One | Condition::Minus => masks.one |= mask,
+            Condition::SameAsPrev => masks.same |= mask,
+            Condition::NotPrev => masks.flip |= mask,
+            Condition::SameAsPrev2 => masks.same2 |= mask,
+            Condition::NotPrev2 => masks.flip2 |= mask,
+        }
+    }
+    masks
+}
+
+/// `Q_{-3}` to `Q_20` of `block` and of its partner under `path`.
+fn pair_states(path: &DifferentialPath, iv: [u32; 4], block: &[u32; 16]) -> ([u32; 24], [u32; 24]) {
+    let (iv_p, block_p) = path.partner(iv, block);
+    (states(iv, block), states(iv_p, &block_p))
+}
+
+/// Set `W_w` of `block` to `word` and recompute the round-1 words after it
+/// so that the states after `Q_{w+1}` keep their values in `q`. Words in
+/// `kept` are left alone, and the states they compute move too. Returns the
+/// round-1 states of the new block and the last index `j` whose `Q_{j+1}`
+/// moved.
+fn move_word(
+    q: &[u32; 24],
+    block: &mut [u32; 16],
+    w: usize,
+    word: u32,
+    kept: u16,
+) -> ([u32; 24], usize) {
+    let mut moved = *q;
+    block[w] = word;
+    let mut last = w;
+    for j in w..16 {
+        if j > last + 4 {
+            break;
+        }
+        let window = [moved[j + 3], moved[j + 2], moved[j + 1], moved[j]];
+        if j == w || kept >> j & 1 == 1 {
+            moved[j + 4] = step(j, window, block[j]);
+            last = j;
+        } else {
+            block[j] = step_inverse(j, window, moved[j + 4]);
+        }
+    }
+    (moved, last)
+}
+
+/// The MD5 register that holds `Q_t`.
+pub fn register(t: isize) -> char {
+    match t.rem_euclid(4) {
+        1 => 'a',
+        2 => 'd',
+        3 => 'c',
+        _ => 'b',
+    }
+}
+
+/// What a [`Violation`] breaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The first message breaks the bit condition.
+    Condition,
+    /// The bit condition holds, but the difference between the two messages
+    /// deviates from the path.
+    Difference,
+}
+
+/// A violated bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub t: isize,
+    /// Bit position, 0-based.
+    pub bit: usize,
+    pub kind: ViolationKind,
+    /// The condition on this bit in the table.
+    pub condition: Condition,
+}
+
+impl Violation {
+    #[inline]
+    pub fn register(&self) -> char {
+        register(self.t)
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let what = match self.kind {
+            ViolationKind::Condition => "condition",
+            ViolationKind::Difference => "difference",
+        };
+        write!(
+            formatter,
+            "Q{:<3} ({}) bit {:>2}: {} '{}'",
+            self.t,
+            self.register(),
+            self.bit + 1,
+            what,
+            self.condition.to_char()
+        )
+    }
+}
+
+/// The result of checking a message block against a condition table.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConditionReport {
+    /// Every violated bit, ordered by step and bit.
+    pub violations: Vec<Violation>,
+}
+
+impl ConditionReport {
+    #[inline]
+    pub fn is_satisfied(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// The first step with a violated bit.
+    pub fn first_violation(&self) -> Option<isize> {
+        self.violations.first().map(|violation| violation.t)
+    }
+
+    /// Number of violated bits per step, indexed from `Q_{-3}`.
+    pub fn per_step(&self) -> [usize; ROWS] {
+        let mut counts = [0; ROWS];
+        for violation in &self.violations {
+            counts[row_index(violation.t)] += 1;
+        }
+        counts
+    }
+}
+
+impl fmt::Display for ConditionReport {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for violation in &self.violations {
+            writeln!(formatter, "{}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+fn row_index(t: isize) -> usize {
+    assert!((FIRST_STEP..=LAST_STEP).contains(&t), "Q_{} is out of range", t);
+    (t - FIRST_STEP) as usize
+}
+
+impl fmt::Display for BitConditions {
+    /// Print every row that has at least one condition.
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for (index, row) in self.rows.iter().enumerate() {
+            if row.iter().all(|&condition| condition == Condition::Free) {
+                continue;
+            }
+            let t = index as isize + FIRST_STEP;
+            write!(formatter, "Q{:<3} ", t)?;
+            for bit in (0..32).rev() {
+                write!(formatter, "{}", row[bit].to_char())?;
+                if bit % 8 == 0 && bit != 0 {
+                    write!(formatter, " ")?;
+                }
+            }
+            writeln!(formatter)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BitConditions {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, formatter)
+    }
+}
+
+/// An error from parsing a [`BitConditions`] table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line number.
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for BitConditions {
+    type Err = ParseError;
+
+    /// Parse a table. Each non-empty line holds a step number, optionally
+    /// prefixed with `Q` and followed by `:`, and 32 symbols from bit 32 down
+    /// to bit 1; whitespace between symbols is ignored. Lines starting with
+    /// `//` are comments, and steps that are not listed have no conditions.
+    fn from_str(table: &str) -> Result<Self, Self::Err> {
+        let mut conditions = BitConditions::default();
+        let mut seen = [false; ROWS];
+        for (number, line) in table.lines().enumerate() {
+            let error = |reason| ParseError {
+                line: number + 1,
+                reason,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let line = line.strip_prefix('Q').unwrap_or(line);
+            let split = line
+                .find(|c: char| c.is_whitespace() || c == ':')
+                .ok_or_else(|| error("missing bit conditions"))?;
+            let t: isize = line[..split]
+                .parse()
+                .map_err(|_| error("invalid step number"))?;
+            if !(FIRST_STEP..=LAST_STEP).contains(&t) {
+                return Err(error("step number out of range"));
+            }
+            if std::mem::replace(&mut seen[row_index(t)], true) {
+                return Err(error("duplicate step"));
+            }
+            let rest = line[split..].trim_start_matches(':');
+            let symbols: Vec<char> = rest.chars().filter(|c| !c.is_whitespace()).collect();
+            if symbols.len() != 32 {
+                return Err(error("expected 32 bit conditions"));
+            }
+            for (i, &symbol) in symbols.iter().enumerate() {
+                let condition =
+                    Condition::from_char(symbol).ok_or_else(|| error("unknown bit condition"))?;
+                conditions.set(t, 31 - i, condition);
+            }
+        }
+        Ok(conditions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "\
+Q1   1...010. ..1..... ....0... ..0.....
+Q2   1^^^110. ..0^^^^^ 0..^1... ^^0..00.
+Q20  ........ ......!+ ........ .......-
+";
+
+    #[test]
+    fn parse_and_print_roundtrip() {
+        let conditions: BitConditions = TABLE.parse().unwrap();
+        assert_eq!(conditions.to_string(), TABLE);
+        assert_eq!(conditions.get(1, 31), Condition::One);
+        assert_eq!(conditions.get(2, 30), Condition::SameAsPrev);
+        assert_eq!(conditions.get(20, 0), Condition::Minus);
+
+        let masks = conditions.masks(20);
+        assert_eq!(masks.zero, 1 << 16);
+        assert_eq!(masks.one, 1);
+        assert_eq!(masks.flip, 1 << 17);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("Q1: 1234".parse::<BitConditions>().unwrap_err().line, 1);
+        assert!("\nQ99 ........ ........ ........ ........"
+            .parse::<BitConditions>()
+            .is_err());
+        assert!("Q1 ........ ........ ........ .......x"
+            .parse::<BitConditions>()
+            .is_err());
+    }
+
+    #[test]
+    fn comments_and_rows_starting_with_hash() {
+        let table = "// Wang's first block\nQ1   ##...... ........ ........ ........\n";
+        let conditions: BitConditions = table.parse().unwrap();
+        assert_eq!(conditions.get(1, 31), Condition::NotPrev2);
+        let error = "##...... ........ ........ ........".parse::<BitConditions>().unwrap_err();
+        assert_eq!(error.line, 1);
+    }
+
+    #[test]
+    fn modification_satisfies_round1() {
+        let conditions: BitConditions = TABLE.parse().unwrap();
+        let iv = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+        let mut block = [0x12345678u32; 16];
+        let q = conditions.modify_round1(iv, &mut block);
+        for t in 1..=16 {
+            let index = t as usize + 3;
+            assert_eq!(conditions.masks(t).violations(q[index], q[index - 1], q[index - 2]), 0);
+        }
+    }
+}
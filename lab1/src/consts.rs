@@ -18,4 +18,16 @@ pub fn m0_p() -> Vec<u8> {
 pub fn m1_p() -> Vec<u8> {
     str_to_bytes("d11d0b96 9c7b41dc f497d8e4 d555655a 479a7335 cfdebf0 66f12930 8fb109d1
     797f2775 eb5cd530 baade822 5c154c79 ddcb74ed 6dd3c55f 580a9bb1 e3a7cc35")
-}
\ No newline at end of file
+}
+/// The chaining value after the published first block `m0`.
+#[cfg(test)]
+pub(crate) fn published_iv() -> [u32; 4] {
+    published_ivs().0
+}
+
+/// The chaining values after the published first blocks `m0` and `m0_p`.
+#[cfg(test)]
+pub(crate) fn published_ivs() -> ([u32; 4], [u32; 4]) {
+    crate::task3::second_block_ivs(&m0(), &m0_p())
+        .expect("the published first blocks yield the second-block IV difference")
+}
@@ -0,0 +1,672 @@
+//! Derivation of sufficient bit conditions from a differential path.
+//!
+//! Every bit position of the working state is handled as a column of
+//! `Q_{-3}[i]` to `Q_64[i]`. For every step `t` the boolean function output
+//! difference `ΔF_t` is chosen so that `ΔT_t = ΔF_t + ΔQ_{t-3} + ΔW_t`
+//! rotates onto `ΔQ_{t+1} - ΔQ_t`, preferring digits that need no new
+//! condition. Each digit is then forced by adding the weakest conditions on
+//! `Q_t`, `Q_{t-1}` and `Q_{t-2}` that leave only the wanted output. A
+//! candidate condition is only accepted if some assignment of the column
+//! still satisfies every condition and every digit chosen so far, so later
+//! steps are never left without a solution.
+
+use core::fmt;
+
+use super::conditions::{BitConditions, Condition, FIRST_STEP, LAST_STEP};
+use super::differential::DifferentialPath;
+use super::md5::{round_function, Trace, ROTATION, WORD_INDEX};
+
+const ROWS: usize = (LAST_STEP - FIRST_STEP + 1) as usize;
+const MSB: usize = 31;
+/// Number of digit choices tried before giving up.
+const SEARCH_BUDGET: usize = 4096;
+
+/// Why a path could not be turned into conditions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DerivationError {
+    /// No choice of `ΔF_t` digits sums to the required modular difference.
+    UnreachableDifference { t: usize },
+    /// No conditions on free bits force the chosen output difference.
+    Contradiction { t: usize, bit: usize },
+}
+
+impl fmt::Display for DerivationError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DerivationError::UnreachableDifference { t } => write!(
+                formatter,
+                "step {}: boolean function cannot produce the required difference",
+                t
+            ),
+            DerivationError::Contradiction { t, bit } => {
+                write!(
+                    formatter,
+                    "step {}, bit {}: no consistent bit conditions",
+                    t,
+                    bit + 1
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DerivationError {}
+
+#[inline]
+fn row(t: isize) -> usize {
+    (t - FIRST_STEP) as usize
+}
+
+/// One bit position of the working state.
+#[derive(Clone)]
+struct Column {
+    bit: usize,
+    symbols: [Condition; ROWS],
+    flips: [bool; ROWS],
+    /// Chosen output difference of each step's boolean function.
+    digits: [Option<i8>; 64],
+    /// Bits of a pair known to follow the path, if any.
+    reference: Option<[u8; ROWS]>,
+}
+
+impl Column {
+    /// Output difference of step `t` for the values `x = Q_t[i]`,
+    /// `y = Q_{t-1}[i]` and `z = Q_{t-2}[i]`. At the MSB only the presence
+    /// of a difference is reported.
+    fn output(&self, t: usize, x: u8, y: u8, z: u8) -> i8 {
+        let flip = |r: isize| self.flips[row(r)] as u8;
+        let t_row = t as isize;
+        let before = round_function(t, x as u32, y as u32, z as u32) & 1;
+        let after = round_function(
+            t,
+            (x ^ flip(t_row)) as u32,
+            (y ^ flip(t_row - 1)) as u32,
+            (z ^ flip(t_row - 2)) as u32,
+        ) & 1;
+        if self.bit == MSB {
+            (before ^ after) as i8
+        } else {
+            after as i8 - before as i8
+        }
+    }
+
+    /// Whether value `v` of `Q_r[i]` satisfies its condition, given
+    /// `Q_{r-1}[i] = prev` and `Q_{r-2}[i] = prev2`.
+    fn allows(&self, r: isize, v: u8, prev: u8, prev2: u8) -> bool {
+        match self.symbols[row(r)] {
+            Condition::Free => true,
+            Condition::Zero | Condition::Plus => v == 0,
+            Condition::One | Condition::Minus => v == 1,
+            Condition::SameAsPrev => r > FIRST_STEP && v == prev,
+            Condition::NotPrev => r > FIRST_STEP && v != prev,
+            Condition::SameAsPrev2 => r > FIRST_STEP + 1 && v == prev2,
+            Condition::NotPrev2 => r > FIRST_STEP + 1 && v != prev2,
+        }
+    }
+
+    /// Whether some assignment of the column satisfies every condition,
+    /// every digit if `with_digits` is set, and the values in `fixed`.
+    fn feasible(&self, with_digits: bool, fixed: &[(isize, u8)]) -> bool {
+        let fixed_value = |r: isize| fixed.iter().find(|(f, _)| *f == r).map(|&(_, v)| v);
+        // state: bit 0 = Q_{r-1}, bit 1 = Q_r
+        let mut reachable = [false; 4];
+        for (state, entry) in reachable.iter_mut().enumerate() {
+            let (prev, current) = ((state & 1) as u8, (state >> 1) as u8);
+            let r0 = FIRST_STEP;
+            let r1 = FIRST_STEP + 1;
+            *entry = fixed_value(r0).is_none_or(|v| v == prev)
+                && fixed_value(r1).is_none_or(|v| v == current)
+                && self.allows(r0, prev, 0, 0)
+                && self.allows(r1, current, prev, 0);
+        }
+        for r in FIRST_STEP + 2..=LAST_STEP {
+            let mut next = [false; 4];
+            for (state, &ok) in reachable.iter().enumerate() {
+                if !ok {
+                    continue;
+                }
+                let (prev2, prev) = ((state & 1) as u8, (state >> 1) as u8);
+                for v in 0..2u8 {
+                    if fixed_value(r).is_some_and(|f| f != v) || !self.allows(r, v, prev, prev2) {
+                        continue;
+                    }
+                    if with_digits && (0..=63).contains(&r) {
+                        if let Some(digit) = self.digits[r as usize] {
+                            if self.output(r as usize, v, prev, prev2) != digit {
+                                continue;
+                            }
+                        }
+                    }
+                    next[(prev | v << 1) as usize] = true;
+                }
+            }
+            reachable = next;
+        }
+        // The digit of step r is checked when Q_r is assigned, so every
+        // digit has been accounted for.
+        reachable.iter().any(|&ok| ok)
+    }
+
+    /// Output differences of step `t` over every assignment of `Q_t`,
+    /// `Q_{t-1}` and `Q_{t-2}` that admits a full column.
+    fn outputs(&self, t: usize, with_digits: bool) -> Vec<i8> {
+        let t_row = t as isize;
+        let mut outputs = Vec::with_capacity(8);
+        for assignment in 0u8..8 {
+            let (x, y, z) = (assignment & 1, assignment >> 1 & 1, assignment >> 2 & 1);
+            if self.feasible(with_digits, &[(t_row, x), (t_row - 1, y), (t_row - 2, z)]) {
+                let output = self.output(t, x, y, z);
+                if !outputs.contains(&output) {
+                    outputs.push(output);
+                }
+            }
+        }
+        outputs
+    }
+
+    /// Whether the reference pair, if any, satisfies `symbol` on row `r`.
+    fn reference_allows(&self, r: isize, symbol: Condition) -> bool {
+        let Some(values) = self.reference else {
+            return true;
+        };
+        let mut trial = self.clone();
+        trial.symbols[row(r)] = symbol;
+        let value = |r: isize| if r < FIRST_STEP { 0 } else { values[row(r)] };
+        trial.allows(r, value(r), value(r - 1), value(r - 2))
+    }
+
+    /// Output difference of step `t` on the reference pair, if any.
+    fn reference_output(&self, t: usize) -> Option<i8> {
+        let values = self.reference?;
+        let t_row = t as isize;
+        let value = |r: isize| if r < FIRST_STEP { 0 } else { values[row(r)] };
+        Some(self.output(t, value(t_row), value(t_row - 1), value(t_row - 2)))
+    }
+
+    /// Whether the conditions alone force the output of step `t` to `digit`.
+    fn forces(&self, t: usize, digit: i8) -> bool {
+        let outputs = self.outputs(t, false);
+        outputs == [digit]
+    }
+}
+
+struct Deriver<'a> {
+    path: &'a DifferentialPath,
+    columns: Vec<Column>,
+}
+
+impl<'a> Deriver<'a> {
+    fn new(path: &'a DifferentialPath, reference: Option<&Trace>) -> Deriver<'a> {
+        let mut columns = Vec::with_capacity(32);
+        for bit in 0..32 {
+            let mut column = Column {
+                bit,
+                symbols: [Condition::Free; ROWS],
+                flips: [false; ROWS],
+                digits: [None; 64],
+                reference: reference.map(|trace| {
+                    let mut values = [0; ROWS];
+                    for t in FIRST_STEP..=LAST_STEP {
+                        values[row(t)] = (trace.q(t) >> bit & 1) as u8;
+                    }
+                    values
+                }),
+            };
+            for t in FIRST_STEP..=LAST_STEP {
+                let bsdr = path.q(t);
+                column.flips[row(t)] = (bsdr.plus | bsdr.minus) >> bit & 1 == 1;
+                if bit != MSB && bsdr.plus >> bit & 1 == 1 {
+                    column.symbols[row(t)] = Condition::Plus;
+                } else if bit != MSB && bsdr.minus >> bit & 1 == 1 {
+                    column.symbols[row(t)] = Condition::Minus;
+                }
+            }
+            columns.push(column);
+        }
+        Deriver { path, columns }
+    }
+
+    /// Candidate digit vectors for `ΔF_t`, cheapest first.
+    ///
+    /// A rotation by `s` maps `ΔT` onto one of `RL(ΔT)`, `RL(ΔT) + 1`,
+    /// `RL(ΔT) - 2^s` or `RL(ΔT) - 2^s + 1`, so every preimage is tried. For
+    /// each, the cheapest digits are followed by the cheapest alternatives
+    /// that avoid one of its digits, which gives the search room to back
+    /// out of choices that later steps cannot live with.
+    fn output_candidates(&self, t: usize) -> Vec<[i8; 32]> {
+        let t_row = t as isize;
+        let wanted = self
+            .path
+            .q(t_row + 1)
+            .modular()
+            .wrapping_sub(self.path.q(t_row).modular());
+        let shift = 1u32 << ROTATION[t];
+
+        // Cost of each output digit: 0 if already forced, 1 if a condition
+        // is needed, None if impossible.
+        let mut costs = [[None::<u32>; 3]; 32];
+        for (column, cost) in self.columns.iter().zip(costs.iter_mut()) {
+            let forced = column.outputs(t, false);
+            let reference = column.reference_output(t);
+            for digit in column.outputs(t, true) {
+                if reference.is_some_and(|r| r != digit) {
+                    continue;
+                }
+                cost[(digit + 1) as usize] = Some(if forced == [digit] { 0 } else { 1 });
+            }
+        }
+
+        let mut candidates: Vec<(u32, [i8; 32])> = Vec::new();
+        for delta in [
+            wanted,
+            wanted.wrapping_sub(1),
+            wanted.wrapping_add(shift),
+            wanted.wrapping_add(shift).wrapping_sub(1),
+        ] {
+            let target = delta
+                .rotate_right(ROTATION[t])
+                .wrapping_sub(self.path.q(t_row - 3).modular())
+                .wrapping_sub(self.path.message[WORD_INDEX[t]]);
+            let Some(best) = cheapest_digits(&costs, target) else {
+                continue;
+            };
+            candidates.push(best);
+            for bit in 0..32 {
+                let mut restricted = costs;
+                restricted[bit][(best.1[bit] + 1) as usize] = None;
+                if let Some(alternative) = cheapest_digits(&restricted, target) {
+                    candidates.push(alternative);
+                }
+            }
+        }
+        candidates.sort_by_key(|&(cost, _)| cost);
+        let mut digits: Vec<[i8; 32]> = Vec::with_capacity(candidates.len());
+        for (_, candidate) in candidates {
+            if !digits.contains(&candidate) {
+                digits.push(candidate);
+            }
+        }
+        digits
+    }
+
+    /// Depth-first search over the digit choices of steps `t..64`.
+    fn solve(&mut self, t: usize, budget: &mut usize) -> Result<(), DerivationError> {
+        if t == 64 {
+            return Ok(());
+        }
+        let mut error = DerivationError::UnreachableDifference { t };
+        for digits in self.output_candidates(t) {
+            if *budget == 0 {
+                break;
+            }
+            *budget -= 1;
+            let saved = self.columns.clone();
+            let forced = self
+                .columns
+                .iter_mut()
+                .zip(digits.iter())
+                .try_for_each(|(column, &digit)| Deriver::force(column, t, digit));
+            match forced.and_then(|_| self.solve(t + 1, budget)) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    error = e;
+                    self.columns = saved;
+                }
+            }
+        }
+        Err(error)
+    }
+
+    /// Add conditions to `column` so that step `t` outputs `digit`.
+    fn force(column: &mut Column, t: usize, digit: i8) -> Result<(), DerivationError> {
+        column.digits[t] = Some(digit);
+        if column.forces(t, digit) {
+            return Ok(());
+        }
+        let candidates = candidates(column, t);
+        let accept = |column: &Column| column.feasible(true, &[]) && column.forces(t, digit);
+        for &(r, symbol) in &candidates {
+            let mut trial = column.clone();
+            trial.symbols[row(r)] = symbol;
+            if accept(&trial) {
+                *column = trial;
+                return Ok(());
+            }
+        }
+        for (i, &(r1, symbol1)) in candidates.iter().enumerate() {
+            for &(r2, symbol2) in &candidates[i + 1..] {
+                if r1 == r2 {
+                    continue;
+                }
+                let mut trial = column.clone();
+                trial.symbols[row(r1)] = symbol1;
+                trial.symbols[row(r2)] = symbol2;
+                if accept(&trial) {
+                    *column = trial;
+                    return Ok(());
+                }
+            }
+        }
+        Err(DerivationError::Contradiction { t, bit: column.bit })
+    }
+}
+
+/// Conditions that may be placed on free bits for step `t`, weakest first.
+/// Conditions on the input chaining value constrain the previous block and
+/// come last.
+fn candidates(column: &Column, t: usize) -> Vec<(isize, Condition)> {
+    let x = t as isize;
+    let mut candidates = Vec::new();
+    for r in (FIRST_STEP..=x).rev().take(5) {
+        if column.symbols[row(r)] != Condition::Free {
+            continue;
+        }
+        let mut symbols = vec![Condition::Zero, Condition::One];
+        if r > FIRST_STEP && r >= x - 1 {
+            symbols.extend([Condition::SameAsPrev, Condition::NotPrev]);
+        }
+        if r > FIRST_STEP + 1 && r == x {
+            symbols.extend([Condition::SameAsPrev2, Condition::NotPrev2]);
+        }
+        for symbol in symbols {
+            if column.reference_allows(r, symbol) {
+                candidates.push((r, symbol));
+            }
+        }
+    }
+    candidates.sort_by_key(|&(r, _)| r <= 0);
+    candidates
+}
+
+/// Cheapest signed digits summing to `target` modulo `2^32`, by dynamic
+/// programming over bits with a carry in `-1..=1`. Ties prefer fewer
+/// non-zero digits.
+fn cheapest_digits(costs: &[[Option<u32>; 3]; 32], target: u32) -> Option<(u32, [i8; 32])> {
+    const INF: u32 = u32::MAX;
+    let mut best = [[INF; 3]; 33];
+    let mut choice = [[(0i8, 0usize); 3]; 33];
+    best[0][1] = 0;
+    for bit in 0..32 {
+        for carry in 0..3 {
+            if best[bit][carry] == INF {
+                continue;
+            }
+            let residual = (target >> bit & 1) as i32 + carry as i32 - 1;
+            for digit in -1i8..=1 {
+                if bit == MSB && digit == -1 {
+                    continue;
+                }
+                let Some(cost) = costs[bit][(digit + 1) as usize] else {
+                    continue;
+                };
+                let rest = residual - digit as i32;
+                if rest.rem_euclid(2) != 0 {
+                    continue;
+                }
+                let next = if bit == MSB {
+                    1
+                } else {
+                    (rest / 2 + 1) as usize
+                };
+                let total = best[bit][carry] + cost * 64 + (digit != 0) as u32;
+                if total < best[bit + 1][next] {
+                    best[bit + 1][next] = total;
+                    choice[bit + 1][next] = (digit, carry);
+                }
+            }
+        }
+    }
+    if best[32][1] == INF {
+        return None;
+    }
+    let mut digits = [0i8; 32];
+    let mut carry = 1;
+    for bit in (0..32).rev() {
+        let (digit, previous) = choice[bit + 1][carry];
+        digits[bit] = digit;
+        carry = previous;
+    }
+    Some((best[32][1], digits))
+}
+
+/// Derive sufficient conditions for `path`.
+///
+/// The result contains `+` and `-` for every signed difference below bit 32
+/// and the conditions on `Q_t`, `Q_{t-1}` and `Q_{t-2}` that make every
+/// boolean function produce a difference the path can use. Rotations are
+/// assumed to behave as chosen, which is not a bit condition.
+pub fn derive_conditions(path: &DifferentialPath) -> Result<BitConditions, DerivationError> {
+    derive(path, None)
+}
+
+/// Derive sufficient conditions for `path` that `reference`, the trace of
+/// the first message of a pair following the path, satisfies.
+///
+/// A path usually admits several condition sets. Steering the choice with a
+/// known pair yields a set that published collisions can be checked against.
+pub fn derive_conditions_for(
+    path: &DifferentialPath,
+    reference: &Trace,
+) -> Result<BitConditions, DerivationError> {
+    derive(path, Some(reference))
+}
+
+fn derive(
+    path: &DifferentialPath,
+    reference: Option<&Trace>,
+) -> Result<BitConditions, DerivationError> {
+    let mut deriver = Deriver::new(path, reference);
+    let mut budget = SEARCH_BUDGET;
+    deriver.solve(0, &mut budget)?;
+    let mut conditions = BitConditions::default();
+    for column in &deriver.columns {
+        for t in FIRST_STEP..=LAST_STEP {
+            conditions.set(t, column.bit, column.symbols[row(t)]);
+        }
+    }
+    Ok(conditions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md5_attack::{
+        wang_first_block, wang_first_block_path, wang_second_block, wang_second_block_path,
+    };
+    use crate::differential::differential_trace;
+    use crate::md5::{bytes_to_words, transform_traced, Context};
+    use crate::consts;
+
+    /// Wang and Yu's sufficient conditions for the first round of the
+    /// second block, as masks of `Q_1` to `Q_16` that are fixed to 0, fixed
+    /// to 1, or equal to the previous step. They are transcribed from the
+    /// paper, not from the derivation.
+    const PUBLISHED_SECOND_BLOCK: [(u32, u32, u32); 16] = [
+        (0x0a000820, 0x84200000, 0x00000000),
+        (0x02208026, 0x8c000800, 0x701f10c0),
+        (0x40201080, 0xbe1f0966, 0x00000018),
+        (0x443b19ee, 0xba040010, 0x00000601),
+        (0xb41011af, 0x482f0e50, 0x00000000),
+        (0x9a1113a9, 0x04220c56, 0x00000000),
+        (0x083201c0, 0x96011e01, 0x01808000),
+        (0x1b810001, 0x843283c0, 0x00000002),
+        (0x03828202, 0x9c0101c1, 0x00001000),
+        (0x00041003, 0x878383c0, 0x00000000),
+        (0x00021000, 0x800583c3, 0x00086000),
+        (0x0007e000, 0x80081080, 0x7f000000),
+        (0xc0000080, 0x3f0fe008, 0x00000000),
+        (0xbf040000, 0x400be088, 0x00000000),
+        (0x82008008, 0x7d000000, 0x00000000),
+        (0x80000000, 0x20000000, 0x00000000),
+    ];
+
+    /// The value `published` fixes for `Q_t[bit]`, if any. The table was
+    /// written for one chaining value, whose `Q_0` is `q0`.
+    fn fixed(published: &[(u32, u32, u32); 16], q0: u32, t: isize, bit: usize) -> Option<u32> {
+        if t == 0 {
+            return Some(q0 >> bit & 1);
+        }
+        let (zero, one, _) = published[t as usize - 1];
+        match (zero >> bit & 1, one >> bit & 1) {
+            (1, _) => Some(0),
+            (_, 1) => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Whether `published` implies `condition` on `Q_t[bit]`, for a step
+    /// of the first round.
+    fn implies(
+        published: &[(u32, u32, u32); 16],
+        q0: u32,
+        t: isize,
+        bit: usize,
+        condition: Condition,
+    ) -> bool {
+        let (here, before) = (fixed(published, q0, t, bit), fixed(published, q0, t - 1, bit));
+        match condition {
+            Condition::Free => true,
+            Condition::Zero | Condition::Plus => here == Some(0),
+            Condition::One | Condition::Minus => here == Some(1),
+            Condition::SameAsPrev => {
+                published[t as usize - 1].2 >> bit & 1 == 1 || (here.is_some() && here == before)
+            }
+            Condition::NotPrev => here.is_some() && before.is_some() && here != before,
+            Condition::SameAsPrev2 | Condition::NotPrev2 => false,
+        }
+    }
+
+    #[test]
+    fn published_second_block_holds_for_published_pair() {
+        // Checks the transcription: the published block must satisfy it.
+        let iv = consts::published_iv();
+        let trace = transform_traced(iv, &bytes_to_words(&consts::m1()));
+        for t in 1..=16 {
+            let (zero, one, same) = PUBLISHED_SECOND_BLOCK[t as usize - 1];
+            let (q, prev) = (trace.q(t), trace.q(t - 1));
+            assert_eq!(q & zero, 0, "Q{}", t);
+            assert_eq!(q & one, one, "Q{}", t);
+            assert_eq!((q ^ prev) & same, 0, "Q{}", t);
+        }
+    }
+
+    #[test]
+    fn second_block_round_one_is_implied_by_published_conditions() {
+        // Wang's table fixes more bits than the path needs, so every
+        // derived condition must follow from it, but not the converse.
+        let iv = consts::published_iv();
+        let reference = transform_traced(iv, &bytes_to_words(&consts::m1()));
+        let derived = derive_conditions_for(wang_second_block_path(), &reference).unwrap();
+        for t in 1..=16 {
+            for bit in 0..32 {
+                let condition = derived.get(t, bit);
+                assert!(
+                    implies(&PUBLISHED_SECOND_BLOCK, reference.q(0), t, bit, condition),
+                    "Q{}[{}] {}",
+                    t,
+                    bit,
+                    condition.to_char()
+                );
+            }
+        }
+    }
+
+    /// The table [`wang_first_block`] derived when this test was written.
+    const FIRST_BLOCK_TABLE: &str = "\
+Q3   ........ ....0... ....0... .0......
+Q4   ........ 0^^^1^^^ ^^^^1^^^ ^^......
+Q5   ^...1.0. ^-++++++ ++++++++ ++1..1.1
+Q6   !^^^0^1^ +1111111 10111100 0-0^^0^^
+Q7   0+++++-- -1111110 1111-+++ ++-+++++
+Q8   00000001 -..-+++- +.0.0101 0100000+
+Q9   !1111011 ...10000 0.1^111- ++1111+-
+Q10  01...... 0..11111 1.+-...0 01....00
+Q11  0+...... ....0001 1^00...0 11....10
+Q12  00....^^ ....-+++ +++1...- +.......
+Q13  01....+- ....1111 111....0 0...1...
+Q14  0.0...00 ....1011 111....1 1...^...
+Q15  ..1...01 ........ -....... ....+...
+Q16  ^.-..... ........ ........ ........
+Q17  ^....... ......0. ^....... ....^...
+Q18  ^.^..... ......1. ........ ........
+Q19  ^....... ......+. ........ ........
+Q20  ^....... ........ ........ ........
+Q21  ^....... ......^. ........ ........
+Q22  ^....... ........ ........ ........
+Q23  0....... ........ ........ ........
+Q24  1....... ........ ........ ........
+Q48  m....... ........ ........ ........
+Q49  m....... ........ ........ ........
+Q50  #....... ........ ........ ........
+Q51  m....... ........ ........ ........
+Q52  m....... ........ ........ ........
+Q53  m....... ........ ........ ........
+Q54  m....... ........ ........ ........
+Q55  m....... ........ ........ ........
+Q56  m....... ........ ........ ........
+Q57  m....... ........ ........ ........
+Q58  m....... ........ ........ ........
+Q59  m....... ........ ........ ........
+Q60  #.....0. ........ ........ ........
+Q61  m.....1. ........ ........ ........
+Q62  m.....+. ........ ........ ........
+Q63  m.....+. ........ ........ ........
+Q64  ......+. ........ ........ ........
+";
+
+    #[test]
+    fn first_block_table_regression() {
+        // Regression check only: the table above was produced by this
+        // derivation, so this catches changes to it, not errors in it.
+        let table: BitConditions = FIRST_BLOCK_TABLE.parse().unwrap();
+        assert_eq!(wang_first_block().to_string(), table.to_string());
+    }
+
+    #[test]
+    fn published_pair_meets_its_derived_conditions() {
+        // No condition table is involved: the path is that of the pair.
+        let iv = Context::new().state();
+        let (m0, m0_p) = (bytes_to_words(&consts::m0()), bytes_to_words(&consts::m0_p()));
+        let path = DifferentialPath::from_trace(&differential_trace(iv, &m0, iv, &m0_p));
+        let derived = derive_conditions(&path).unwrap();
+        let report = derived.check(&path, iv, &m0);
+        assert!(report.is_satisfied(), "{:?}", report);
+    }
+
+    #[test]
+    fn signs_every_difference() {
+        let path = wang_first_block_path();
+        let derived = derive_conditions(path).unwrap();
+        for t in FIRST_STEP..=LAST_STEP {
+            let bsdr = path.q(t);
+            for bit in 0..MSB {
+                let expected = if bsdr.plus >> bit & 1 == 1 {
+                    Condition::Plus
+                } else if bsdr.minus >> bit & 1 == 1 {
+                    Condition::Minus
+                } else {
+                    continue;
+                };
+                assert_eq!(derived.get(t, bit), expected, "Q{}[{}]", t, bit);
+            }
+        }
+    }
+
+    #[test]
+    fn second_block_table_regression() {
+        let iv = consts::published_iv();
+        let block = bytes_to_words(&consts::m1());
+        let reference = transform_traced(iv, &block);
+        let derived = derive_conditions_for(wang_second_block_path(), &reference).unwrap();
+        assert!(derived.check(wang_second_block_path(), iv, &block).is_satisfied());
+        // Regression check only: rows Q-3 to Q0 and Q17 to Q64 of the table
+        // were produced by this derivation. Q1 to Q16 are Wang's rows, which
+        // fix bits that the derivation leaves free; they are checked by
+        // second_block_round_one_is_implied_by_published_conditions.
+        for t in (FIRST_STEP..=0).chain(17..=LAST_STEP) {
+            for bit in 0..32 {
+                assert_eq!(derived.get(t, bit), wang_second_block().get(t, bit), "Q{}[{}]", t, bit);
+            }
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! Differences between two compression function evaluations.
+//!
+//! Differences are given both as modular differences `x' - x` and in the
+//! binary signed digit representation (BSDR) `x'_i - x_i`, printed in Wang's
+//! notation: `[-7,+8,-32]` means bit 7 goes from 1 to 0, bit 8 from 0 to 1
+//! and bit 32 from 1 to 0, counting bits from 1.
+
+use core::fmt;
+
+use super::md5::{transform_traced, Trace};
+
+/// A signed-bit difference.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bsdr {
+    /// Bits that are 0 in `x` and 1 in `x'`.
+    pub plus: u32,
+    /// Bits that are 1 in `x` and 0 in `x'`.
+    pub minus: u32,
+}
+
+impl Bsdr {
+    /// The signed-bit difference from `x` to `x_p`.
+    #[inline]
+    pub fn between(x: u32, x_p: u32) -> Bsdr {
+        Bsdr {
+            plus: !x & x_p,
+            minus: x & !x_p,
+        }
+    }
+
+    /// The modular difference this BSDR represents.
+    #[inline]
+    pub fn modular(&self) -> u32 {
+        self.plus.wrapping_sub(self.minus)
+    }
+
+    /// Number of non-zero digits.
+    #[inline]
+    pub fn weight(&self) -> u32 {
+        (self.plus | self.minus).count_ones()
+    }
+
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        self.plus | self.minus == 0
+    }
+}
+
+impl fmt::Display for Bsdr {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "[")?;
+        let mut first = true;
+        for bit in 0..32 {
+            let sign = if self.plus >> bit & 1 == 1 {
+                '+'
+            } else if self.minus >> bit & 1 == 1 {
+                '-'
+            } else {
+                continue;
+            };
+            if !first {
+                write!(formatter, ",")?;
+            }
+            write!(formatter, "{}{}", sign, bit + 1)?;
+            first = false;
+        }
+        write!(formatter, "]")
+    }
+}
+
+/// The difference in one working-state word `Q_t`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepDifference {
+    pub t: isize,
+    pub modular: u32,
+    pub bsdr: Bsdr,
+}
+
+/// Differences in `Q_{-3}` to `Q_64` between two compression evaluations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DifferentialTrace {
+    pub trace: Trace,
+    pub trace_p: Trace,
+    pub steps: Vec<StepDifference>,
+    /// Modular differences of the message words.
+    pub message: [u32; 16],
+}
+
+impl DifferentialTrace {
+    /// Return the difference in `Q_t` for `t` in `-3..=64`.
+    #[inline]
+    pub fn q(&self, t: isize) -> &StepDifference {
+        &self.steps[(t + 3) as usize]
+    }
+
+    /// Return the first `t` whose modular difference is not `expected(t)`.
+    pub fn first_divergence<F: Fn(isize) -> u32>(&self, expected: F) -> Option<isize> {
+        self.steps
+            .iter()
+            .find(|step| step.modular != expected(step.t))
+            .map(|step| step.t)
+    }
+
+    /// Modular difference of the output chaining values.
+    pub fn output(&self) -> [u32; 4] {
+        let mut output = [0u32; 4];
+        for (i, word) in output.iter_mut().enumerate() {
+            *word = self.trace_p.state[i].wrapping_sub(self.trace.state[i]);
+        }
+        output
+    }
+}
+
+impl fmt::Display for DifferentialTrace {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        for step in &self.steps {
+            if step.t >= 1 {
+                let index = self.trace.steps[step.t as usize - 1].word_index;
+                writeln!(
+                    formatter,
+                    "Q{:<3} {:08x} {:<24} m{:<2} {:08x}",
+                    step.t, step.modular, step.bsdr.to_string(), index, self.message[index]
+                )?;
+            } else {
+                writeln!(formatter, "Q{:<3} {:08x} {}", step.t, step.modular, step.bsdr)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Signed modular differences `m'_i - m_i` of the sixteen message words.
+///
+/// Words are numbered as in the compression function, that is after the
+/// block is read little-endian. Each word lies in `-2^31..=2^31`, so it
+/// names one modular difference, with either sign for `2^31`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MessageDifference {
+    words: [i64; 16],
+}
+
+impl MessageDifference {
+    /// Largest magnitude of a word, `2^31`.
+    pub const LIMIT: i64 = 1 << 31;
+
+    /// Returns `None` if a word lies outside `-2^31..=2^31`.
+    pub const fn new(words: [i64; 16]) -> Option<MessageDifference> {
+        let mut i = 0;
+        while i < 16 {
+            if words[i] < -Self::LIMIT || words[i] > Self::LIMIT {
+                return None;
+            }
+            i += 1;
+        }
+        Some(MessageDifference { words })
+    }
+
+    #[inline]
+    pub fn words(&self) -> &[i64; 16] {
+        &self.words
+    }
+
+    /// The differences modulo `2^32`.
+    pub fn modular(&self) -> [u32; 16] {
+        self.words.map(|word| word as u32)
+    }
+
+    /// `block` plus the difference.
+    pub fn apply(&self, block: &[u32; 16]) -> [u32; 16] {
+        let modular = self.modular();
+        core::array::from_fn(|i| block[i].wrapping_add(modular[i]))
+    }
+}
+
+impl fmt::Display for MessageDifference {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "[")?;
+        let mut first = true;
+        for (i, &word) in self.words.iter().enumerate().filter(|(_, &word)| word != 0) {
+            if !first {
+                write!(formatter, ", ")?;
+            }
+            let sign = if word < 0 { '-' } else { '+' };
+            write!(formatter, "m{} {}{:#x}", i, sign, word.unsigned_abs())?;
+            first = false;
+        }
+        write!(formatter, "]")
+    }
+}
+
+/// A message difference that does not match the one of a path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DifferenceMismatch {
+    /// The first word that differs.
+    pub word: usize,
+    /// Modular difference the path expects in that word.
+    pub expected: u32,
+    /// Modular difference given.
+    pub actual: u32,
+}
+
+impl fmt::Display for DifferenceMismatch {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "message difference in m{} is {:#010x}, but the path needs {:#010x}",
+            self.word, self.actual, self.expected
+        )
+    }
+}
+
+impl std::error::Error for DifferenceMismatch {}
+
+/// A differential path: the signed difference of every `Q_t` and the
+/// modular difference of every message word.
+///
+/// Since `2^31 = -2^31` modulo `2^32`, the sign of a bit-32 difference
+/// carries no information and is ignored when comparing against a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DifferentialPath {
+    /// Differences of `Q_{-3}` to `Q_64`.
+    pub q: [Bsdr; 68],
+    /// Modular differences of the message words.
+    pub message: [u32; 16],
+}
+
+impl DifferentialPath {
+    /// The path followed by a concrete pair.
+    pub fn from_trace(diff: &DifferentialTrace) -> DifferentialPath {
+        let mut q = [Bsdr::default(); 68];
+        for (bsdr, step) in q.iter_mut().zip(diff.steps.iter()) {
+            *bsdr = step.bsdr;
+        }
+        DifferentialPath {
+            q,
+            message: diff.message,
+        }
+    }
+
+    /// Return the difference of `Q_t` for `t` in `-3..=64`.
+    #[inline]
+    pub fn q(&self, t: isize) -> Bsdr {
+        self.q[(t + 3) as usize]
+    }
+
+    /// Modular difference of the chaining value the path starts from.
+    pub fn iv(&self) -> [u32; 4] {
+        [
+            self.q(-3).modular(),
+            self.q(0).modular(),
+            self.q(-1).modular(),
+            self.q(-2).modular(),
+        ]
+    }
+
+    /// `iv` and `block` plus the chaining value and message differences of
+    /// the path: the second message of the pair that `block` starts.
+    pub fn partner(&self, iv: [u32; 4], block: &[u32; 16]) -> ([u32; 4], [u32; 16]) {
+        let iv_diff = self.iv();
+        let iv_p = core::array::from_fn(|i| iv[i].wrapping_add(iv_diff[i]));
+        let block_p = core::array::from_fn(|i| block[i].wrapping_add(self.message[i]));
+        (iv_p, block_p)
+    }
+
+    /// Check that `difference` is the message difference of the path.
+    pub fn check_message(&self, difference: &MessageDifference) -> Result<(), DifferenceMismatch> {
+        let actual = difference.modular();
+        match (0..16).find(|&i| actual[i] != self.message[i]) {
+            Some(word) => Err(DifferenceMismatch {
+                word,
+                expected: self.message[word],
+                actual: actual[word],
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Return the bits where `actual` deviates from the difference of `Q_t`.
+    pub fn deviations(&self, t: isize, actual: Bsdr) -> u32 {
+        const MSB: u32 = 1 << 31;
+        let expected = self.q(t);
+        let signed = (actual.plus ^ expected.plus) | (actual.minus ^ expected.minus);
+        let msb = (actual.plus | actual.minus) ^ (expected.plus | expected.minus);
+        (signed & !MSB) | (msb & MSB)
+    }
+}
+
+/// Trace `(iv, block)` and `(iv_p, block_p)` side by side.
+pub fn differential_trace(
+    iv: [u32; 4],
+    block: &[u32; 16],
+    iv_p: [u32; 4],
+    block_p: &[u32; 16],
+) -> DifferentialTrace {
+    let trace = transform_traced(iv, block);
+    let trace_p = transform_traced(iv_p, block_p);
+    let steps = (-3..=64)
+        .map(|t| {
+            let (q, q_p) = (trace.q(t), trace_p.q(t));
+            StepDifference {
+                t,
+                modular: q_p.wrapping_sub(q),
+                bsdr: Bsdr::between(q, q_p),
+            }
+        })
+        .collect();
+    let mut message = [0u32; 16];
+    for (i, word) in message.iter_mut().enumerate() {
+        *word = block_p[i].wrapping_sub(block[i]);
+    }
+    DifferentialTrace {
+        trace,
+        trace_p,
+        steps,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consts, md5};
+
+    #[test]
+    fn bsdr_notation() {
+        let bsdr = Bsdr {
+            plus: 1 << 7,
+            minus: 1 << 6 | 1 << 31,
+        };
+        assert_eq!(bsdr.to_string(), "[-7,+8,-32]");
+        assert_eq!(bsdr.modular(), (1u32 << 7).wrapping_sub(1 << 6).wrapping_sub(1 << 31));
+        assert_eq!(Bsdr::default().to_string(), "[]");
+    }
+
+    #[test]
+    fn message_difference_notation() {
+        let mut words = [0; 16];
+        words[4] = 1 << 31;
+        words[11] = -(1 << 15);
+        let difference = MessageDifference::new(words).unwrap();
+        assert_eq!(difference.to_string(), "[m4 +0x80000000, m11 -0x8000]");
+        assert_eq!(difference.modular()[11], 0xffff8000);
+        assert_eq!(difference.apply(&[1; 16])[4], 0x80000001);
+    }
+
+    #[test]
+    fn message_difference_rejects_wrapping_words() {
+        let mut words = [0; 16];
+        words[0] = -(1 << 31);
+        assert!(MessageDifference::new(words).is_some());
+        words[0] = 1 << 32;
+        assert_eq!(MessageDifference::new(words), None);
+        words[0] = -(1 << 31) - 1;
+        assert_eq!(MessageDifference::new(words), None);
+    }
+
+    #[test]
+    fn second_block_collision_cancels() {
+        let (iv, iv_p) = consts::published_ivs();
+        let diff = differential_trace(
+            iv,
+            &md5::bytes_to_words(&consts::m1()),
+            iv_p,
+            &md5::bytes_to_words(&consts::m1_p()),
+        );
+        assert_eq!(diff.output(), [0; 4]);
+        assert_eq!(diff.q(-3).modular, 0x80000000);
+        assert_eq!(diff.message[4], 0x80000000);
+    }
+}
@@ -0,0 +1,122 @@
+//! HMAC-MD5 ([RFC 2104]).
+//!
+//! ## Example
+//!
+//! ```
+//! use lab1::hmac;
+//!
+//! let tag = hmac::hmac_md5(b"Jefe", b"what do ya want for nothing?");
+//! assert_eq!(format!("{:x}", tag), "750c783e6ab0b503eaa86e310a5db738");
+//! ```
+//!
+//! [RFC 2104]: https://tools.ietf.org/html/rfc2104
+
+use super::md5::{self, Context, Digest};
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+/// A streaming HMAC-MD5 computation.
+#[derive(Clone)]
+pub struct HmacMd5 {
+    inner: Context,
+    outer: Context,
+}
+
+impl HmacMd5 {
+    /// Create an HMAC context keyed with `key`. Keys longer than one block
+    /// are hashed first.
+    pub fn new<T: AsRef<[u8]>>(key: T) -> HmacMd5 {
+        let key = key.as_ref();
+        let mut block = [0u8; 64];
+        if key.len() > 64 {
+            block[..16].copy_from_slice(&*md5::compute(key));
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = Context::new();
+        let mut outer = Context::new();
+        inner.consume(block.map(|byte| byte ^ IPAD));
+        outer.consume(block.map(|byte| byte ^ OPAD));
+        HmacMd5 { inner, outer }
+    }
+
+    /// Consume message data.
+    #[inline]
+    pub fn update<T: AsRef<[u8]>>(&mut self, data: T) {
+        self.inner.consume(data);
+    }
+
+    /// Finalize and return the tag.
+    pub fn finalize(self) -> Digest {
+        let HmacMd5 { inner, mut outer } = self;
+        outer.consume(*inner.compute());
+        outer.compute()
+    }
+
+    /// Finalize and compare the tag with `expected` in constant time.
+    pub fn verify(self, expected: &[u8]) -> bool {
+        let tag = self.finalize();
+        if expected.len() != tag.len() {
+            return false;
+        }
+        let diff = tag
+            .iter()
+            .zip(expected.iter())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y));
+        diff == 0
+    }
+}
+
+/// Compute the HMAC-MD5 tag of `message` under `key`.
+#[inline]
+pub fn hmac_md5<K: AsRef<[u8]>, M: AsRef<[u8]>>(key: K, message: M) -> Digest {
+    let mut hmac = HmacMd5::new(key);
+    hmac.update(message);
+    hmac.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc2202_test_cases() {
+        let key4: Vec<u8> = (0x01..=0x19).collect();
+        let vectors: [(&[u8], &[u8], &str); 7] = [
+            (&[0x0b; 16], b"Hi There", "9294727a3638bb1c13f48ef8158bfc9d"),
+            (b"Jefe", b"what do ya want for nothing?", "750c783e6ab0b503eaa86e310a5db738"),
+            (&[0xaa; 16], &[0xdd; 50], "56be34521d144c88dbb8c733f0e8b3f6"),
+            (&key4, &[0xcd; 50], "697eaf0aca3a3aea3a75164746ffaa79"),
+            (&[0x0c; 16], b"Test With Truncation", "56461ef2342edc00f9bab995690efd4c"),
+            (
+                &[0xaa; 80],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "6b1ab7fe4bd7bf8f0b62e6ce61b9d0cd",
+            ),
+            (
+                &[0xaa; 80],
+                b"Test Using Larger Than Block-Size Key and Larger Than One Block-Size Data",
+                "6f630fad67cda0ee1fb1f562db3aa53e",
+            ),
+        ];
+        for (key, message, expected) in vectors.iter() {
+            assert_eq!(format!("{:x}", hmac_md5(key, message)), *expected);
+        }
+    }
+
+    #[test]
+    fn streaming_and_verify() {
+        let mut hmac = HmacMd5::new(b"Jefe");
+        hmac.update(b"what do ya want ");
+        hmac.update(b"for nothing?");
+        let tag = hmac_md5(b"Jefe", b"what do ya want for nothing?");
+        assert!(hmac.clone().verify(&*tag));
+
+        let mut wrong = *tag;
+        wrong[15] ^= 1;
+        assert!(!hmac.clone().verify(&wrong));
+        assert!(!hmac.verify(&tag[..8]));
+    }
+}
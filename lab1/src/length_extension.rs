@@ -0,0 +1,131 @@
+//! Length-extension attack on the secret-prefix MAC `MD5(key || msg)`.
+//!
+//! Knowing only `MD5(secret)` and `secret.len()`, anyone can compute
+//! `MD5(secret || glue || extension)` for any `extension`, where `glue` is the
+//! MD5 padding of `secret`.
+
+use super::md5::{Context, Digest};
+
+/// A forged message suffix together with its digest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Forgery {
+    /// Padding that the original message was implicitly hashed with.
+    pub glue: Vec<u8>,
+    /// Digest of `original || glue || extension`.
+    pub digest: Digest,
+}
+
+impl Forgery {
+    /// The bytes to append to the original message: `glue || extension`.
+    pub fn suffix(&self, extension: &[u8]) -> Vec<u8> {
+        let mut suffix = self.glue.clone();
+        suffix.extend_from_slice(extension);
+        suffix
+    }
+}
+
+/// Return the MD5 padding for a message of `message_len` bytes.
+pub fn padding(message_len: u64) -> Vec<u8> {
+    let k = (message_len % 64) as usize;
+    let zeros = if k < 56 { 55 - k } else { 119 - k };
+    let mut glue = Vec::with_capacity(zeros + 9);
+    glue.push(0x80);
+    glue.resize(zeros + 1, 0);
+    glue.extend_from_slice(&message_len.wrapping_shl(3).to_le_bytes());
+    glue
+}
+
+/// Recover the chaining value that produced `digest`.
+pub fn digest_to_state(digest: &Digest) -> [u32; 4] {
+    let mut state = [0u32; 4];
+    for (word, bytes) in state.iter_mut().zip(digest.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    state
+}
+
+/// Forge the digest of `original || glue || extension` from the digest of
+/// `original` and its length in bytes.
+///
+/// Returns `None` if `original || glue` would be longer than `u64::MAX`
+/// bytes.
+pub fn extend(digest: &Digest, original_len: u64, extension: &[u8]) -> Option<Forgery> {
+    let glue = padding(original_len);
+    let glued_len = original_len.checked_add(glue.len() as u64)?;
+    let mut context = Context::with_state(digest_to_state(digest), glued_len)
+        .expect("glued length is block aligned");
+    context.consume(extension);
+    Some(Forgery {
+        glue,
+        digest: context.compute(),
+    })
+}
+
+/// A secret-prefix MAC oracle, `MD5(key || msg)`.
+pub struct SecretPrefixMac {
+    key: Vec<u8>,
+}
+
+impl SecretPrefixMac {
+    pub fn new<T: AsRef<[u8]>>(key: T) -> SecretPrefixMac {
+        SecretPrefixMac {
+            key: key.as_ref().to_vec(),
+        }
+    }
+
+    /// Compute the tag of `message`.
+    pub fn sign<T: AsRef<[u8]>>(&self, message: T) -> Digest {
+        let mut context = Context::new();
+        context.consume(&self.key);
+        context.consume(message);
+        context.compute()
+    }
+
+    /// Check that `tag` is the tag of `message`.
+    pub fn verify<T: AsRef<[u8]>>(&self, message: T, tag: &Digest) -> bool {
+        self.sign(message) == *tag
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md5;
+
+    #[test]
+    fn padding_matches_compute() {
+        for len in [0u64, 1, 55, 56, 63, 64, 119, 120] {
+            let message = vec![0x41u8; len as usize];
+            let glue = padding(len);
+            assert_eq!((len + glue.len() as u64) % 64, 0);
+
+            let forgery = extend(&md5::compute(&message), len, b"").unwrap();
+            let mut glued = message.clone();
+            glued.extend_from_slice(&glue);
+            assert_eq!(forgery.digest, md5::compute(&glued));
+        }
+    }
+
+    #[test]
+    fn forge_secret_prefix_mac() {
+        let oracle = SecretPrefixMac::new(b"correct horse battery");
+        let message = b"user=guest&role=user";
+        let tag = oracle.sign(message);
+
+        let extension = b"&role=admin";
+        let key_len = 21;
+        let forgery = extend(&tag, key_len + message.len() as u64, extension).unwrap();
+
+        let mut forged = message.to_vec();
+        forged.extend_from_slice(&forgery.suffix(extension));
+        assert!(oracle.verify(&forged, &forgery.digest));
+    }
+
+    #[test]
+    fn rejects_overflowing_length() {
+        let digest = md5::compute(b"");
+        assert_eq!(extend(&digest, u64::MAX, b"x"), None);
+        assert_eq!(extend(&digest, u64::MAX - 8, b"x"), None);
+        assert!(extend(&digest, u64::MAX - 63 - 9, b"x").is_some());
+    }
+}
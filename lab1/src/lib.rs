@@ -0,0 +1,15 @@
+pub mod utils;
+pub mod consts;
+pub mod md5;
+pub mod md5_attack;
+pub mod conditions;
+pub mod differential;
+pub mod length_extension;
+pub mod hmac;
+pub mod tunnels;
+pub mod derivation;
+pub mod collide;
+pub mod progress;
+pub mod checkpoint;
+pub mod task2;
+pub mod task3;
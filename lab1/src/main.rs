@@ -1,11 +1,169 @@
-mod utils;
-mod consts;
-mod md5;
-mod md5_attack;
-mod task2;
-mod task3;
-
-fn main() {   
-    task2::run();
-    task3::multi_thread_find_m1_m1_p();
+use lab1::checkpoint::Checkpoint;
+use lab1::task3::{Limits, SearchConfig};
+use lab1::utils::parse_u64;
+use lab1::{collide, md5, task2, task3};
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{env, fs, process};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+const USAGE: &str = "usage: lab1 [--seed <n>] [--timeout <seconds>] [--attempts <n>] \
+                     [--histogram <csv>] [--checkpoint <file>] \
+                     [collide <prefix> <out> <out_p> [suffix]]\n       \
+                     lab1 --resume <checkpoint> [--timeout <seconds>] [--attempts <n>] \
+                     [--histogram <csv>] [--checkpoint <file>]";
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let seed =
+        take_option(&mut args, "--seed").map(|value| parse_u64(&value).unwrap_or_else(|| usage()));
+    let histogram = take_option(&mut args, "--histogram").map(PathBuf::from);
+    let limits = Limits {
+        timeout: take_option(&mut args, "--timeout")
+            .map(|value| Duration::from_secs(value.parse().unwrap_or_else(|_| usage()))),
+        attempts: take_option(&mut args, "--attempts")
+            .map(|value| value.parse().unwrap_or_else(|_| usage())),
+    };
+    let checkpoint = take_option(&mut args, "--checkpoint").map(PathBuf::from);
+    let resume = take_option(&mut args, "--resume").map(PathBuf::from);
+
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let handler = interrupt.clone();
+    // A second interrupt does not wait for the checkpoint.
+    ctrlc::set_handler(move || {
+        if handler.swap(true, Ordering::Relaxed) {
+            process::exit(130);
+        }
+    })
+    .expect("no other interrupt handler is installed");
+
+    let config = match &resume {
+        Some(path) => {
+            if seed.is_some() || !args.is_empty() {
+                usage();
+            }
+            let saved = read_checkpoint(path);
+            let master = saved.master;
+            args = saved.command.clone();
+            SearchConfig {
+                threads: saved.workers.len(),
+                limits: Limits {
+                    timeout: limits.timeout.or(saved.limits.timeout),
+                    attempts: limits.attempts.or(saved.limits.attempts),
+                },
+                progress: Some(PROGRESS_INTERVAL),
+                histogram: histogram.or_else(|| saved.histogram.clone()),
+                // A resumed search goes on saving where it was saved.
+                checkpoint: Some(checkpoint.unwrap_or_else(|| path.clone())),
+                interrupt: Some(interrupt.clone()),
+                command: args.clone(),
+                resume: Some(saved),
+                ..SearchConfig::new(master)
+            }
+        }
+        None => SearchConfig {
+            limits,
+            progress: Some(PROGRESS_INTERVAL),
+            histogram,
+            checkpoint,
+            interrupt: Some(interrupt.clone()),
+            command: args.clone(),
+            ..SearchConfig::new(seed.unwrap_or_else(|| fastrand::u64(..)))
+        },
+    };
+
+    match args.first().map(String::as_str) {
+        None => {
+            task2::run();
+            println!("master seed {:#018x}", config.master);
+            match task3::multi_thread_find_m1_m1_p(&config) {
+                Some(found) => {
+                    println!("m1: {:x?}", found.block);
+                    println!("m1_p: {:x?}", found.block_p);
+                    println!("seed {:#018x}, iteration {}", found.seed, found.iteration);
+                }
+                None => println!("no collision within the limits"),
+            }
+        }
+        Some("collide") if (4..=5).contains(&args.len()) => run_collide(&args[1..], &config),
+        _ => usage(),
+    }
+
+    if interrupt.load(Ordering::Relaxed) {
+        match &config.checkpoint {
+            Some(path) => eprintln!("interrupted; continue with lab1 --resume {}", path.display()),
+            None => eprintln!("interrupted; run with --checkpoint <file> to be able to resume"),
+        }
+        process::exit(130);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// Remove `name` and its value from `args`, and return the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == name)?;
+    if i + 1 >= args.len() {
+        usage();
+    }
+    let value = args.remove(i + 1);
+    args.remove(i);
+    Some(value)
+}
+
+fn read_checkpoint(path: &Path) -> Checkpoint {
+    let text = fs::read_to_string(path).unwrap_or_else(|error| {
+        eprintln!("cannot read {}: {}", path.display(), error);
+        process::exit(1);
+    });
+    text.parse().unwrap_or_else(|error| {
+        eprintln!("{}: {}", path.display(), error);
+        process::exit(1);
+    })
+}
+
+fn run_collide(args: &[String], config: &SearchConfig) {
+    let read = |path: &str| {
+        fs::read(path).unwrap_or_else(|error| {
+            eprintln!("cannot read {}: {}", path, error);
+            process::exit(1);
+        })
+    };
+    let prefix = read(&args[0]);
+    let suffix = args.get(3).map(|path| read(path)).unwrap_or_default();
+
+    println!("master seed {:#018x}", config.master);
+    let found = collide::collide(&prefix, &suffix, config).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    let Some(collision) = found else {
+        if !config
+            .interrupt
+            .as_ref()
+            .is_some_and(|interrupt| interrupt.load(Ordering::Relaxed))
+        {
+            eprintln!("no collision within the limits");
+            process::exit(1);
+        }
+        return;
+    };
+    for (name, found) in [("first", &collision.first), ("second", &collision.second)] {
+        println!("{} block: seed {:#018x}, iteration {}", name, found.seed, found.iteration);
+    }
+
+    for (path, data) in [(&args[1], &collision.message), (&args[2], &collision.message_p)] {
+        if let Err(error) = fs::write(path, data) {
+            eprintln!("cannot write {}: {}", path, error);
+            process::exit(1);
+        }
+        println!("{:x}  {}", md5::compute(data), path);
+    }
 }
@@ -3,6 +3,8 @@
 //! ## Example
 //!
 //! ```
+//! use lab1::md5;
+//!
 //! let digest = md5::compute(b"abcdefghijklmnopqrstuvwxyz");
 //! assert_eq!(format!("{:x}", digest), "c3fcd3d76192e4007dfb496cca67e13b");
 //! ```
@@ -34,6 +36,9 @@ use core::convert;
 use core::fmt;
 use core::ops;
 
+#[cfg(feature = "std")]
+use std::io;
+
 use super::md5_attack::transform_attack;
 
 /// A digest.
@@ -89,17 +94,17 @@ implement!(UpperHex, "{:02X}");
 /// A context.
 #[derive(Clone)]
 pub struct Context {
-    pub buffer: [u8; 64],
-    pub count: [u32; 2],
-    pub state: [u32; 4],
+    buffer: [u8; 64],
+    count: [u32; 2],
+    state: [u32; 4],
 }
 
-// const PADDING: [u8; 64] = [
-//     0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-//     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-//     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-//     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-// ];
+const PADDING: [u8; 64] = [
+    0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
 
 impl Context {
     /// Create a context for computing a digest.
@@ -112,49 +117,91 @@ impl Context {
         }
     }
 
-    /// Consume data.
-    #[cfg(target_pointer_width = "32")]
+    /// Create a context that resumes hashing from an intermediate chaining
+    /// value after `bytes_processed` bytes of input.
+    ///
+    /// Returns `None` unless `bytes_processed` is a multiple of 64, since
+    /// only whole blocks are reflected in the chaining value.
+    pub fn with_state(state: [u32; 4], bytes_processed: u64) -> Option<Context> {
+        if !bytes_processed.is_multiple_of(64) {
+            return None;
+        }
+        let bits = bytes_processed.wrapping_shl(3);
+        Some(Context {
+            buffer: [0; 64],
+            count: [bits as u32, (bits >> 32) as u32],
+            state,
+        })
+    }
+
+    /// Return the current chaining value.
     #[inline]
-    pub fn consume<T: AsRef<[u8]>>(&mut self, data: T) {
-        consume(self, data.as_ref());
+    pub fn state(&self) -> [u32; 4] {
+        self.state
+    }
+
+    /// Return the number of bytes consumed so far, modulo 2^61.
+    #[inline]
+    pub fn bytes_processed(&self) -> u64 {
+        ((self.count[1] as u64) << 32 | self.count[0] as u64) >> 3
     }
 
     /// Consume data.
-    #[cfg(target_pointer_width = "64")]
-    pub fn consume<T: AsRef<[u8]>>(&mut self, data: T, attack : bool) {
-        for chunk in data.as_ref().chunks(core::u32::MAX as usize) {
-            consume(self, chunk, attack);
+    pub fn consume<T: AsRef<[u8]>>(&mut self, data: T) {
+        // The bit count is updated with 32-bit arithmetic, so feed at most
+        // `u32::MAX` bytes at a time. On 32-bit targets this is a single chunk.
+        for chunk in data.as_ref().chunks(u32::MAX as usize) {
+            consume(self, chunk);
         }
     }
 
+    /// Consume one block, letting [`transform_attack`] rewrite it first.
+    ///
+    /// Returns the block that was actually hashed together with the new
+    /// chaining value. Returns `None` and leaves the context unchanged if it
+    /// holds a partial block, or if the block could not be brought onto the
+    /// path.
+    pub fn consume_attack(&mut self, block: &[u8; 64]) -> Option<([u8; 64], [u32; 4])> {
+        if (self.count[0] >> 3) & 0x3f != 0 {
+            return None;
+        }
+        let mut input = bytes_to_words(block);
+        let mut state = self.state;
+        if !transform_attack(&mut state, &mut input) {
+            return None;
+        }
+        self.state = state;
+        self.count[0] = self.count[0].wrapping_add(512);
+        if self.count[0] < 512 {
+            self.count[1] = self.count[1].wrapping_add(1);
+        }
+        Some((words_to_bytes(&input), self.state))
+    }
+
     /// Finalize and return the digest.
-    pub fn compute(self) -> Digest {
-        //let mut input = [0u32; 16];
-        //let k = ((self.count[0] >> 3) & 0x3f) as usize;
-        //input[14] = self.count[0];
-        //input[15] = self.count[1];
-        // consume(
-        //     &mut self,
-        //     &PADDING[..(if k < 56 { 56 - k } else { 120 - k })],
-        // );
-        // let mut j = 0;
-        // for i in 0..16 {
-        //     input[i] = ((self.buffer[j + 3] as u32) << 24) |
-        //                ((self.buffer[j + 2] as u32) << 16) |
-        //                ((self.buffer[j + 1] as u32) <<  8) |
-        //                ((self.buffer[j    ] as u32)      );
-        //     j += 4;
-        // }
-        // transform(&mut self.state, &input);
+    pub fn compute(mut self) -> Digest {
+        let count = self.count;
+        let k = ((count[0] >> 3) & 0x3f) as usize;
+        consume(
+            &mut self,
+            &PADDING[..(if k < 56 { 56 - k } else { 120 - k })],
+        );
+        let mut input = bytes_to_words(&self.buffer);
+        input[14] = count[0];
+        input[15] = count[1];
+        transform(&mut self.state, &input);
+        let mut digest = [0u8; 16];
+        for (bytes, value) in digest.chunks_exact_mut(4).zip(self.state.iter()) {
+            bytes.copy_from_slice(&value.to_le_bytes());
+        }
+        Digest(digest)
+    }
+
+    /// Return the current chaining value without padding, serialized big-endian.
+    ///
+    /// Useful for comparing intermediate states after whole blocks.
+    pub fn chaining_value(self) -> Digest {
         let mut digest = [0u8; 16];
-        // let mut j = 0;
-        // for i in 0..4 {
-        //     digest[j    ] = ((self.state[i]      ) & 0xff) as u8;
-        //     digest[j + 1] = ((self.state[i] >>  8) & 0xff) as u8;
-        //     digest[j + 2] = ((self.state[i] >> 16) & 0xff) as u8;
-        //     digest[j + 3] = ((self.state[i] >> 24) & 0xff) as u8;
-        //     j += 4;
-        // }
         let mut j = 0;
         for i in 0..4 {
             let bytes = self.state[i].to_be_bytes();
@@ -165,6 +212,13 @@ impl Context {
     }
 }
 
+impl Default for Context {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl convert::From<Context> for Digest {
     #[inline]
     fn from(context: Context) -> Digest {
@@ -186,13 +240,31 @@ impl io::Write for Context {
     }
 }
 
-// /// Compute the digest of data.
-// #[inline]
-// pub fn compute<T: AsRef<[u8]>>(data: T) -> Digest {
-//     let mut context = Context::new();
-//     context.consume(data);
-//     context.compute()
-// }
+/// Compute the digest of data.
+#[inline]
+pub fn compute<T: AsRef<[u8]>>(data: T) -> Digest {
+    let mut context = Context::new();
+    context.consume(data);
+    context.compute()
+}
+
+/// Read a 64-byte block as 16 little-endian words.
+pub fn bytes_to_words(block: &[u8]) -> [u32; 16] {
+    let mut words = [0u32; 16];
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    words
+}
+
+/// Write 16 words as a 64-byte block, little-endian.
+pub fn words_to_bytes(words: &[u32; 16]) -> [u8; 64] {
+    let mut block = [0u8; 64];
+    for (bytes, word) in block.chunks_exact_mut(4).zip(words.iter()) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    block
+}
 
 fn consume(
     Context {
@@ -201,9 +273,7 @@ fn consume(
         state,
     }: &mut Context,
     data: &[u8],
-    attack : bool
 ) {
-    let mut input = [0u32; 16];
     let mut k = ((count[0] >> 3) & 0x3f) as usize;
     let length = data.len() as u32;
     count[0] = count[0].wrapping_add(length << 3);
@@ -215,19 +285,7 @@ fn consume(
         buffer[k] = value;
         k += 1;
         if k == 0x40 {
-            let mut j = 0;
-            for i in 0..16 {
-                input[i] = ((buffer[j + 3] as u32) << 24) |
-                           ((buffer[j + 2] as u32) << 16) |
-                           ((buffer[j + 1] as u32) <<  8) |
-                           ((buffer[j    ] as u32)      );
-                j += 4;
-            }
-            if attack {
-                transform_attack(state, &mut input)
-            } else {
-                transform(state, &input);
-            }
+            transform(state, &bytes_to_words(buffer));
             k = 0;
         }
     }
@@ -239,7 +297,7 @@ pub fn transform(state: &mut [u32; 4], input: &[u32; 16]) {
         ($a:expr, $b:expr) => ($a.wrapping_add($b));
     );
     macro_rules! rotate(
-        ($x:expr, $n:expr) => (($x << $n) | ($x >> (32 - $n)));
+        ($x:expr, $n:expr) => ($x.rotate_left($n));
     );
     {
         macro_rules! F(
@@ -373,4 +431,257 @@ pub fn transform(state: &mut [u32; 4], input: &[u32; 16]) {
     state[1] = add!(state[1], b);
     state[2] = add!(state[2], c);
     state[3] = add!(state[3], d);
-}
\ No newline at end of file
+}
+
+/// Message word index used by each of the 64 steps.
+pub const WORD_INDEX: [usize; 64] = [
+     0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14, 15,
+     1,  6, 11,  0,  5, 10, 15,  4,  9, 14,  3,  8, 13,  2,  7, 12,
+     5,  8, 11, 14,  1,  4,  7, 10, 13,  0,  3,  6,  9, 12, 15,  2,
+     0,  7, 14,  5, 12,  3, 10,  1,  8, 15,  6, 13,  4, 11,  2,  9,
+];
+
+/// Left rotation applied in each of the 64 steps.
+pub const ROTATION: [u32; 64] = [
+     7, 12, 17, 22,  7, 12, 17, 22,  7, 12, 17, 22,  7, 12, 17, 22,
+     5,  9, 14, 20,  5,  9, 14, 20,  5,  9, 14, 20,  5,  9, 14, 20,
+     4, 11, 16, 23,  4, 11, 16, 23,  4, 11, 16, 23,  4, 11, 16, 23,
+     6, 10, 15, 21,  6, 10, 15, 21,  6, 10, 15, 21,  6, 10, 15, 21,
+];
+
+/// Additive constant of each of the 64 steps.
+pub const ADDITIVE_CONSTANT: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+/// The boolean function of step `t`: F, G, H or I.
+#[inline]
+pub fn round_function(t: usize, x: u32, y: u32, z: u32) -> u32 {
+    match t / 16 {
+        0 => (x & y) | (!x & z),
+        1 => (x & z) | (y & !z),
+        2 => x ^ y ^ z,
+        _ => y ^ (x | !z),
+    }
+}
+
+/// Compute `Q_{t+1}` from `Q_t`, `Q_{t-1}`, `Q_{t-2}`, `Q_{t-3}` and the
+/// message word of step `t`.
+#[inline]
+pub fn step(t: usize, q: [u32; 4], word: u32) -> u32 {
+    let [q0, q1, q2, q3] = q;
+    let sum = round_function(t, q0, q1, q2)
+        .wrapping_add(q3)
+        .wrapping_add(ADDITIVE_CONSTANT[t])
+        .wrapping_add(word);
+    q0.wrapping_add(sum.rotate_left(ROTATION[t]))
+}
+
+/// Recover the message word of step `t` from `Q_t`, `Q_{t-1}`, `Q_{t-2}`,
+/// `Q_{t-3}` and the resulting `Q_{t+1}`.
+#[inline]
+pub fn step_inverse(t: usize, q: [u32; 4], next: u32) -> u32 {
+    let [q0, q1, q2, q3] = q;
+    next.wrapping_sub(q0)
+        .rotate_right(ROTATION[t])
+        .wrapping_sub(round_function(t, q0, q1, q2))
+        .wrapping_sub(q3)
+        .wrapping_sub(ADDITIVE_CONSTANT[t])
+}
+
+/// `Q_{-3}` to `Q_{N-4}` of `block` from chaining value `iv`, oldest first:
+/// the chaining value and the states of the first `N - 4` steps.
+pub fn states<const N: usize>(iv: [u32; 4], block: &[u32; 16]) -> [u32; N] {
+    let mut q = [0u32; N];
+    q[..4].copy_from_slice(&[iv[0], iv[3], iv[2], iv[1]]);
+    for t in 0..N - 4 {
+        let window = [q[t + 3], q[t + 2], q[t + 1], q[t]];
+        q[t + 4] = step(t, window, block[WORD_INDEX[t]]);
+    }
+    q
+}
+
+/// One step of a [`Trace`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TraceStep {
+    /// The working-state value `Q_{t+1}` produced by this step.
+    pub q: u32,
+    /// Index of the message word used by this step.
+    pub word_index: usize,
+    /// The message word used by this step.
+    pub word: u32,
+    /// Left rotation amount.
+    pub rotation: u32,
+    /// Additive constant.
+    pub constant: u32,
+}
+
+/// The working states of one compression function evaluation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trace {
+    /// The input chaining value.
+    pub initial: [u32; 4],
+    /// The 64 steps, in order.
+    pub steps: [TraceStep; 64],
+    /// The output chaining value.
+    pub state: [u32; 4],
+}
+
+impl Trace {
+    /// Return `Q_t` for `t` in `-3..=64`, where `Q_{-3}`, `Q_0`, `Q_{-1}`
+    /// and `Q_{-2}` are the input words a, b, c and d.
+    pub fn q(&self, t: isize) -> u32 {
+        match t {
+            -3 => self.initial[0],
+            -2 => self.initial[3],
+            -1 => self.initial[2],
+            0 => self.initial[1],
+            1..=64 => self.steps[t as usize - 1].q,
+            _ => panic!("Q_{} is out of range", t),
+        }
+    }
+}
+
+/// Run the compression function, recording every step.
+pub fn transform_traced(state: [u32; 4], input: &[u32; 16]) -> Trace {
+    let mut steps = [TraceStep::default(); 64];
+    // Q_t, Q_{t-1}, Q_{t-2}, Q_{t-3}
+    let mut q = [state[1], state[2], state[3], state[0]];
+    for (t, entry) in steps.iter_mut().enumerate() {
+        let word = input[WORD_INDEX[t]];
+        let next = step(t, q, word);
+        *entry = TraceStep {
+            q: next,
+            word_index: WORD_INDEX[t],
+            word,
+            rotation: ROTATION[t],
+            constant: ADDITIVE_CONSTANT[t],
+        };
+        q = [next, q[0], q[1], q[2]];
+    }
+    let output = [
+        state[0].wrapping_add(q[3]),
+        state[1].wrapping_add(q[0]),
+        state[2].wrapping_add(q[1]),
+        state[3].wrapping_add(q[2]),
+    ];
+    Trace {
+        initial: state,
+        steps,
+        state: output,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compute, transform, transform_traced, Context};
+
+    fn hex(data: &[u8]) -> String {
+        format!("{:x}", compute(data))
+    }
+
+    #[test]
+    fn rfc1321_test_suite() {
+        let vectors: [(&[u8], &str); 7] = [
+            (b"", "d41d8cd98f00b204e9800998ecf8427e"),
+            (b"a", "0cc175b9c0f1b6a831c399e269772661"),
+            (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (b"message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (b"abcdefghijklmnopqrstuvwxyz", "c3fcd3d76192e4007dfb496cca67e13b"),
+            (
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "d174ab98d277d9f5a5611c2c9f419d9f",
+            ),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ];
+        for (input, expected) in vectors.iter() {
+            assert_eq!(hex(input), *expected);
+        }
+    }
+
+    #[test]
+    fn chunked_consume_matches_single() {
+        let data = [0x61u8; 200];
+        let mut context = Context::new();
+        for chunk in data.chunks(17) {
+            context.consume(chunk);
+        }
+        assert_eq!(format!("{:x}", context.compute()), hex(&data));
+    }
+
+    #[test]
+    fn resume_from_state() {
+        let data = [0x33u8; 150];
+        let mut prefix = Context::new();
+        prefix.consume(&data[..128]);
+        assert_eq!(prefix.bytes_processed(), 128);
+
+        let mut resumed = Context::with_state(prefix.state(), 128).unwrap();
+        resumed.consume(&data[128..]);
+        assert_eq!(resumed.compute(), compute(&data[..]));
+
+        assert!(Context::with_state(prefix.state(), 100).is_none());
+    }
+
+    #[test]
+    fn consume_attack_returns_hashed_block() {
+        let mut start = Context::new();
+        start.consume(crate::consts::m0());
+        let rng = fastrand::Rng::with_seed(5);
+        let (mut hashed, mut failed) = (0, 0);
+        for _ in 0..200 {
+            let mut block = [0u8; 64];
+            rng.fill(&mut block);
+            let mut context = start.clone();
+            let Some((message, state)) = context.consume_attack(&block) else {
+                failed += 1;
+                assert_eq!(context.state(), start.state());
+                assert_eq!(context.compute(), start.clone().compute());
+                continue;
+            };
+            hashed += 1;
+            assert_eq!(state, context.state());
+
+            let mut plain = start.clone();
+            plain.consume(&message[..]);
+            assert_eq!(plain.state(), state);
+            assert_eq!(context.compute(), plain.compute());
+        }
+        assert!(hashed > 0 && failed > 0);
+
+        let mut partial = Context::new();
+        partial.consume(b"abc");
+        assert!(partial.consume_attack(&[0xa5; 64]).is_none());
+    }
+
+    #[test]
+    fn traced_matches_transform() {
+        let input: [u32; 16] = core::array::from_fn(|i| (i as u32).wrapping_mul(0x9e3779b9));
+        let mut state = Context::new().state();
+        let trace = transform_traced(state, &input);
+        transform(&mut state, &input);
+        assert_eq!(trace.state, state);
+        assert_eq!(trace.q(64), state[1].wrapping_sub(trace.q(0)));
+        assert_eq!(trace.steps[17].word, input[6]);
+        assert_eq!(trace.steps[17].rotation, 9);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn io_copy_into_context() {
+        let data = [0x5au8; 1000];
+        let mut context = Context::new();
+        std::io::copy(&mut &data[..], &mut context).unwrap();
+        assert_eq!(context.compute(), compute(&data[..]));
+    }
+}
@@ -1,292 +1,320 @@
-pub fn transform_attack(state: &mut [u32; 4], input: &mut [u32; 16]) {
-    let (mut a, mut b, mut c, mut d) = (state[0], state[1], state[2], state[3]);
-    let (mut a_prev, mut b_prev, mut c_prev, mut d_prev) = (state[0], state[1], state[2], state[3]);
+//! Message modification for Wang's two-block collision paths.
 
-    macro_rules! add(
-        ($a:expr, $b:expr) => ($a.wrapping_add($b));
-    );
-    macro_rules! sub(
-        ($a:expr, $b:expr) => ($a.wrapping_sub($b));
-    );
-    macro_rules! rotate_left(
-        ($x:expr, $n:expr) => (($x << $n) | ($x >> (32 - $n)));
-    );
-    macro_rules! rotate_right(
-        ($x:expr, $n:expr) => (($x >> $n) | ($x << (32 - $n)));
-    );
-    {
-        macro_rules! FIX_0(
-            ($x:expr, $mask:expr) => ($x &= !$mask);
-        );
-        macro_rules! FIX_1(
-            ($x:expr, $mask:expr) => ($x |= $mask);
-        );
-        macro_rules! FIX_PREV(
-            ($x:expr, $mask:expr, $x_prev:expr) => ($x = ($x & !$mask) | ($x_prev & $mask));
-        );
-        macro_rules! F(
-            ($x:expr, $y:expr, $z:expr) => (($x & $y) | (!$x & $z));
-        );
-        macro_rules! T(
-            ($a:expr, $b:expr, $c:expr, $d:expr, $x:expr, $s:expr, $ac:expr) => ({
-                $a = add!(add!(add!($a, F!($b, $c, $d)), $x), $ac);
-                $a = rotate_left!($a, $s);
-                $a = add!($a, $b);
-            });
+use std::sync::OnceLock;
+
+use super::conditions::{BitConditions, ConditionReport};
+use super::consts;
+use super::derivation::derive_conditions_for;
+use super::differential::{differential_trace, Bsdr, DifferentialPath};
+use super::md5::{bytes_to_words, step, transform, transform_traced, Context, WORD_INDEX};
+
+/// Sufficient conditions of the second-block path, for all 64 steps.
+///
+/// The path is the one followed by the published collision in `consts`; see
+/// [`wang_second_block_path`]. The first round is Wang's original table,
+/// except that bit 32 is tied to the previous step instead of fixed, and
+/// `Q_{-2}` to `Q_0` carry the conditions a chaining value needs. Wang's
+/// table was written for the published chaining value; without these
+/// changes a chaining value with the right difference may admit no block
+/// that follows the first round. The rows from `Q_17` on are what
+/// [`derive_conditions_for`](crate::derivation::derive_conditions_for)
+/// derives from the published pair.
+pub const WANG_SECOND_BLOCK: &str = "\
+Q-2  ......+. ........ ........ ........
+Q-1  ^....+-. ........ ........ ........
+Q0   ^....0+. ........ ........ ..0.....
+Q1   !...010. ..1..... ....0... ..0.....
+Q2   ^^^^110. ..0^^^^^ 0..^1... ^^0..00.
+Q3   ^011111. ..011111 ...01..1 011^^11.
+Q4   ^011101. ..000100 ...00^^0 0001000^
+Q5   !10010.. ..101111 ...01110 01010000
+Q6   ^..0010. ..10..10 ...01100 01010110
+Q7   !..1011^ ^.00..01 ^..11110 00.....1
+Q8   ^..00100 0.11..10 1.....11 11....^0
+Q9   ^..11100 0.....01 0..^..01 11....01
+Q10  ^....111 1....011 1..0..11 11....00
+Q11  ^....... ....^101 1^^0..11 11....11
+Q12  ^^^^^^^^ ....1000 0001.... 1.......
+Q13  !0111111 ....1111 111..... 0...1...
+Q14  ^1000000 ....1011 111..... 1...1...
+Q15  ^1111101 ........ 0....... ....0...
+Q16  ^.1..... ........ ........ ........
+Q17  ^....... ......0. ^....... ....^...
+Q18  ^.^..... ......1. ........ ........
+Q19  ^....... ......+. ........ ........
+Q20  ^....... ........ ........ ........
+Q21  ^....... ......^. ........ ........
+Q22  ^....... ........ ........ ........
+Q23  0....... ........ ........ ........
+Q24  1....... ........ ........ ........
+Q48  m....... ........ ........ ........
+Q49  m....... ........ ........ ........
+Q50  #....... ........ ........ ........
+Q51  m....... ........ ........ ........
+Q52  m....... ........ ........ ........
+Q53  m....... ........ ........ ........
+Q54  m....... ........ ........ ........
+Q55  m....... ........ ........ ........
+Q56  m....... ........ ........ ........
+Q57  m....... ........ ........ ........
+Q58  m....... ........ ........ ........
+Q59  m....... ........ ........ ........
+Q60  #.....0. ........ ........ ........
+Q61  m....01. ........ ........ ........
+Q62  m.....-. ........ ........ ........
+Q63  m....-+. ........ ........ ........
+Q64  ......-. ........ ........ ........
+";
+
+/// Sufficient conditions of the first-block path, for all 64 steps.
+///
+/// They are derived by [`derive_conditions_for`] from
+/// [`wang_first_block_path`], steered by the trace of the published first
+/// block from the MD5 IV. They place no condition on the chaining value,
+/// so the path can be followed from any IV.
+pub fn wang_first_block() -> &'static BitConditions {
+    static CONDITIONS: OnceLock<BitConditions> = OnceLock::new();
+    CONDITIONS.get_or_init(|| {
+        let reference = transform_traced(Context::new().state(), &bytes_to_words(&consts::m0()));
+        derive_conditions_for(wang_first_block_path(), &reference)
+            .expect("the published pair follows its own path")
+    })
+}
+
+/// The differential path of Wang's published first-block pair.
+pub fn wang_first_block_path() -> &'static DifferentialPath {
+    static PATH: OnceLock<DifferentialPath> = OnceLock::new();
+    PATH.get_or_init(|| {
+        let iv = Context::new().state();
+        let diff = differential_trace(
+            iv,
+            &bytes_to_words(&consts::m0()),
+            iv,
+            &bytes_to_words(&consts::m0_p()),
         );
-        macro_rules! T_INV(
-            ($a:expr, $a_prev:expr, $b:expr, $c:expr, $d:expr, $x:expr, $s:expr, $ac:expr) => ({
-                $x = sub!($a, $b);
-                $x = rotate_right!($x, $s);
-                $x = sub!($x, add!(add!(F!($b, $c, $d), $ac), $a_prev));
-            });
+        DifferentialPath::from_trace(&diff)
+    })
+}
+
+/// The parsed [`WANG_SECOND_BLOCK`] table.
+pub fn wang_second_block() -> &'static BitConditions {
+    static CONDITIONS: OnceLock<BitConditions> = OnceLock::new();
+    CONDITIONS.get_or_init(|| WANG_SECOND_BLOCK.parse().unwrap())
+}
+
+/// The differential path of Wang's published second-block pair.
+pub fn wang_second_block_path() -> &'static DifferentialPath {
+    static PATH: OnceLock<DifferentialPath> = OnceLock::new();
+    PATH.get_or_init(|| {
+        let mut context = Context::new();
+        context.consume(consts::m0());
+        let mut context_p = Context::new();
+        context_p.consume(consts::m0_p());
+        let diff = differential_trace(
+            context.state(),
+            &bytes_to_words(&consts::m1()),
+            context_p.state(),
+            &bytes_to_words(&consts::m1_p()),
         );
+        DifferentialPath::from_trace(&diff)
+    })
+}
 
-        const S1: u32 =  7;
-        const S2: u32 = 12;
-        const S3: u32 = 17;
-        const S4: u32 = 2;
+/// Check `block` from chaining value `iv` against all 64 steps of the
+/// second-block path.
+pub fn check_conditions(iv: [u32; 4], block: &[u32; 16]) -> ConditionReport {
+    wang_second_block().check(wang_second_block_path(), iv, block)
+}
 
-        const A1_0 : u32 = 0x0a000820;
-        const A1_1 : u32 = 0x84200000;
-        const D1_0 : u32 = 0x02208026;
-        const D1_1 : u32 = 0x8c000800;
-        const D1_P : u32 = 0x701f10c0;
-        const C1_0 : u32 = 0x40201080;
-        const C1_1 : u32 = 0xbe1f0966;
-        const C1_P : u32 = 0x00000018;
-        const B1_0 : u32 = 0x443b19ee;
-        const B1_1 : u32 = 0xba040010;
-        const B1_P : u32 = 0x00000601;
-        const A2_0 : u32 = 0xb41011af;
-        const A2_1 : u32 = 0x482f0e50;
-        const D2_0 : u32 = 0x9a1113a9;
-        const D2_1 : u32 = 0x04220c56;
-        const C2_0 : u32 = 0x083201c0;
-        const C2_1 : u32 = 0x96011e01;
-        const C2_P : u32 = 0x01808000;
-        const B2_0 : u32 = 0x1b810001;
-        const B2_1 : u32 = 0x843283c0;
-        const B2_P : u32 = 0x00000002;
-        const A3_0 : u32 = 0x03828202;
-        const A3_1 : u32 = 0x9c0101c1;
-        const A3_P : u32 = 0x00001000;
-        const D3_0 : u32 = 0x00041003;
-        const D3_1 : u32 = 0x878383c0;
-        const C3_0 : u32 = 0x00021000;
-        const C3_1 : u32 = 0x800583c3;
-        const C3_P : u32 = 0x00086000;
-        const B3_0 : u32 = 0x0007e000;
-        const B3_1 : u32 = 0x80081080;
-        const B3_P : u32 = 0x7f000000;
-        const A4_0 : u32 = 0xc0000080;
-        const A4_1 : u32 = 0x3f0fe008;
-        const D4_0 : u32 = 0xbf040000;
-        const D4_1 : u32 = 0x400be088;
-        const C4_0 : u32 = 0x82008008;
-        const C4_1 : u32 = 0x7d000000;
-        const B4_0 : u32 = 0x80000000;
-        const B4_1 : u32 = 0x20000000;
+/// Rewrite `input` so that it follows the second-block path through `Q_20`,
+/// then run the compression function on it.
+///
+/// The first round is fixed by single-message modification and steps 17 to
+/// 20 by multi-message modification; see [`BitConditions::follow_round1`]
+/// and [`BitConditions::modify_round2`]. Returns whether both succeeded.
+/// `state` is updated either way.
+pub fn transform_attack(state: &mut [u32; 4], input: &mut [u32; 16]) -> bool {
+    transform_attack_with(wang_second_block(), wang_second_block_path(), state, input)
+}
+
+/// Like [`transform_attack`], with the conditions and path given
+/// explicitly.
+pub fn transform_attack_with(
+    conditions: &BitConditions,
+    path: &DifferentialPath,
+    state: &mut [u32; 4],
+    input: &mut [u32; 16],
+) -> bool {
+    let modified =
+        conditions.follow_round1(path, *state, input) && conditions.modify_round2(path, *state, input);
+    transform(state, input);
+    modified
+}
 
-        T! (a, b, c, d, input[ 0], S1, 3614090360); /* 1 */
-        FIX_0!(a,A1_0);
-        FIX_1!(a,A1_1);
-        T_INV! (a, a_prev, b, c, d, input[ 0], S1, 3614090360);
-        a_prev = a;
-        
-        T! (d, a, b, c, input[ 1], S2, 3905402710); /* 2 */
-        FIX_0!(d,D1_0);
-        FIX_1!(d,D1_1);
-        FIX_PREV!(d,D1_P,a);
-        T_INV! (d, d_prev, a, b, c, input[ 1], S2, 3905402710);
-        d_prev = d;
-      
-        T! (c, d, a, b, input[ 2], S3, 606105819); /* 3 */
-        FIX_0!(c,C1_0);
-        FIX_1!(c,C1_1);
-        FIX_PREV!(c,C1_P,d);
-        T_INV! (c, c_prev, d, a, b, input[ 2], S3, 606105819);
-        c_prev = c;
-      
-        T! (b, c, d, a, input[ 3], S4, 3250441966); /* 4 */
-        FIX_0!(b,B1_0);
-        FIX_1!(b,B1_1);
-        FIX_PREV!(b,B1_P,c);
-        T_INV! (b, b_prev, c, d, a, input[ 3], S4, 3250441966);
-        b_prev = b;
-      
-        T! (a, b, c, d, input[ 4], S1, 4118548399); /* 5 */
-        FIX_0!(a,A2_0);
-        FIX_1!(a,A2_1);
-        T_INV! (a, a_prev, b, c, d, input[ 4], S1, 4118548399);
-        a_prev = a;
-      
-        T! (d, a, b, c, input[ 5], S2, 1200080426); /* 6 */
-        FIX_0!(d,D2_0);
-        FIX_1!(d,D2_1);
-        T_INV! (d, d_prev, a, b, c, input[ 5], S2, 1200080426);
-        d_prev = d;
-      
-        T! (c, d, a, b, input[ 6], S3, 2821735955); /* 7 */
-        FIX_0!(c,C2_0);
-        FIX_1!(c,C2_1);
-        FIX_PREV!(c,C2_P,d);
-        T_INV! (c, c_prev, d, a, b, input[ 6], S3, 2821735955);
-        c_prev = c;
-      
-        T! (b, c, d, a, input[ 7], S4, 4249261313); /* 8 */
-        FIX_0!(b,B2_0);
-        FIX_1!(b,B2_1);
-        FIX_PREV!(b,B2_P,c);
-        T_INV! (b, b_prev, c, d, a, input[ 7], S4, 4249261313);
-        b_prev = b;
-      
-        T! (a, b, c, d, input[ 8], S1, 1770035416); /* 9 */
-        FIX_0!(a,A3_0);
-        FIX_1!(a,A3_1);
-        FIX_PREV!(a,A3_P,b);
-        T_INV! (a, a_prev, b, c, d, input[ 8], S1, 1770035416);
-        a_prev = a;
-      
-        T! (d, a, b, c, input[ 9], S2, 2336552879); /* 10 */
-        FIX_0!(d,D3_0);
-        FIX_1!(d,D3_1);
-        T_INV! (d, d_prev, a, b, c, input[ 9], S2, 2336552879);
-        d_prev = d;
-      
-        T! (c, d, a, b, input[10], S3, 4294925233); /* 11 */
-        FIX_0!(c,C3_0);
-        FIX_1!(c,C3_1);
-        FIX_PREV!(c,C3_P,d);
-        T_INV! (c, c_prev, d, a, b, input[10], S3, 4294925233);
-        c_prev = c;
-      
-        T! (b, c, d, a, input[11], S4, 2304563134); /* 12 */
-        FIX_0!(b,B3_0);
-        FIX_1!(b,B3_1);
-        FIX_PREV!(b,B3_P,c);
-        T_INV! (b, b_prev, c, d, a, input[11], S4, 2304563134);
-        b_prev = b;
-      
-        T! (a, b, c, d, input[12], S1, 1804603682); /* 13 */
-        FIX_0!(a,A4_0);
-        FIX_1!(a,A4_1);
-        T_INV! (a, a_prev, b, c, d, input[12], S1, 1804603682);
-      
-        T! (d, a, b, c, input[13], S2, 4254626195); /* 14 */
-        FIX_0!(d,D4_0);
-        FIX_1!(d,D4_1);
-        T_INV! (d, d_prev, a, b, c, input[13], S2, 4254626195);
-      
-        T! (c, d, a, b, input[14], S3, 2792965006); /* 15 */
-        FIX_0!(c,C4_0);
-        FIX_1!(c,C4_1);
-        T_INV! (c, c_prev, d, a, b, input[14], S3, 2792965006);
-      
-        T! (b, c, d, a, input[15], S4, 1236535329); /* 16 */
-        FIX_0!(b,B4_0);
-        FIX_1!(b,B4_1);
-        T_INV! (b, b_prev, c, d, a, input[15], S4, 1236535329);
+/// How a candidate pair ended in [`evaluate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// `Q_t` broke its conditions or left the path; `t` is in `1..=64`.
+    Abort(usize),
+    /// The pair followed the path to the end, and its chaining values
+    /// differ by this modular difference.
+    Complete([u32; 4]),
+}
+
+impl Outcome {
+    /// Whether both blocks lead to the same chaining value.
+    #[inline]
+    pub fn is_collision(&self) -> bool {
+        *self == Outcome::Complete([0; 4])
     }
-    {
-        macro_rules! F(
-            ($x:expr, $y:expr, $z:expr) => (($x & $z) | ($y & !$z));
-        );
-        macro_rules! T(
-            ($a:expr, $b:expr, $c:expr, $d:expr, $x:expr, $s:expr, $ac:expr) => ({
-                $a = add!(add!(add!($a, F!($b, $c, $d)), $x), $ac);
-                $a = rotate_left!($a, $s);
-                $a = add!($a, $b);
-            });
-        );
-        const S1: u32 =  5;
-        const S2: u32 =  9;
-        const S3: u32 = 14;
-        const S4: u32 = 20;
-        T!(a, b, c, d, input[ 1], S1, 4129170786);
-        T!(d, a, b, c, input[ 6], S2, 3225465664);
-        T!(c, d, a, b, input[11], S3,  643717713);
-        T!(b, c, d, a, input[ 0], S4, 3921069994);
-        T!(a, b, c, d, input[ 5], S1, 3593408605);
-        T!(d, a, b, c, input[10], S2,   38016083);
-        T!(c, d, a, b, input[15], S3, 3634488961);
-        T!(b, c, d, a, input[ 4], S4, 3889429448);
-        T!(a, b, c, d, input[ 9], S1,  568446438);
-        T!(d, a, b, c, input[14], S2, 3275163606);
-        T!(c, d, a, b, input[ 3], S3, 4107603335);
-        T!(b, c, d, a, input[ 8], S4, 1163531501);
-        T!(a, b, c, d, input[13], S1, 2850285829);
-        T!(d, a, b, c, input[ 2], S2, 4243563512);
-        T!(c, d, a, b, input[ 7], S3, 1735328473);
-        T!(b, c, d, a, input[12], S4, 2368359562);
+}
+
+/// Hash `block` from `iv` and `block_p` from `iv_p` in lockstep, stopping
+/// at the first step that leaves the second-block path.
+pub fn evaluate(iv: [u32; 4], iv_p: [u32; 4], block: &[u32; 16], block_p: &[u32; 16]) -> Outcome {
+    evaluate_with(wang_second_block(), wang_second_block_path(), iv, iv_p, block, block_p)
+}
+
+/// Like [`evaluate`], with the conditions and path given explicitly.
+///
+/// After each step `Q_{t+1}` of `block` is checked against its row of
+/// `conditions` and the difference of the pair against `path`. Most
+/// candidates fail early in round 2, so this is far cheaper than two full
+/// [`transform`] calls. The conditions on the chaining values are not
+/// checked.
+pub fn evaluate_with(
+    conditions: &BitConditions,
+    path: &DifferentialPath,
+    iv: [u32; 4],
+    iv_p: [u32; 4],
+    block: &[u32; 16],
+    block_p: &[u32; 16],
+) -> Outcome {
+    // Q_t, Q_{t-1}, Q_{t-2}, Q_{t-3}
+    let mut q = [iv[1], iv[2], iv[3], iv[0]];
+    let mut q_p = [iv_p[1], iv_p[2], iv_p[3], iv_p[0]];
+    for (t, &index) in WORD_INDEX.iter().enumerate() {
+        let next = step(t, q, block[index]);
+        let next_p = step(t, q_p, block_p[index]);
+        let row = t as isize + 1;
+        if conditions.masks(row).violations(next, q[0], q[1]) != 0
+            || path.deviations(row, Bsdr::between(next, next_p)) != 0
+        {
+            return Outcome::Abort(t + 1);
+        }
+        q = [next, q[0], q[1], q[2]];
+        q_p = [next_p, q_p[0], q_p[1], q_p[2]];
     }
-    {
-        macro_rules! F(
-            ($x:expr, $y:expr, $z:expr) => ($x ^ $y ^ $z);
-        );
-        macro_rules! T(
-            ($a:expr, $b:expr, $c:expr, $d:expr, $x:expr, $s:expr, $ac:expr) => ({
-                $a = add!(add!(add!($a, F!($b, $c, $d)), $x), $ac);
-                $a = rotate_left!($a, $s);
-                $a = add!($a, $b);
-            });
-        );
-        const S1: u32 =  4;
-        const S2: u32 = 11;
-        const S3: u32 = 16;
-        const S4: u32 = 23;
-        T!(a, b, c, d, input[ 5], S1, 4294588738);
-        T!(d, a, b, c, input[ 8], S2, 2272392833);
-        T!(c, d, a, b, input[11], S3, 1839030562);
-        T!(b, c, d, a, input[14], S4, 4259657740);
-        T!(a, b, c, d, input[ 1], S1, 2763975236);
-        T!(d, a, b, c, input[ 4], S2, 1272893353);
-        T!(c, d, a, b, input[ 7], S3, 4139469664);
-        T!(b, c, d, a, input[10], S4, 3200236656);
-        T!(a, b, c, d, input[13], S1,  681279174);
-        T!(d, a, b, c, input[ 0], S2, 3936430074);
-        T!(c, d, a, b, input[ 3], S3, 3572445317);
-        T!(b, c, d, a, input[ 6], S4,   76029189);
-        T!(a, b, c, d, input[ 9], S1, 3654602809);
-        T!(d, a, b, c, input[12], S2, 3873151461);
-        T!(c, d, a, b, input[15], S3,  530742520);
-        T!(b, c, d, a, input[ 2], S4, 3299628645);
+    // The final window is Q_64, Q_63, Q_62, Q_61, that is b, c, d, a.
+    let output = |iv: [u32; 4], q: [u32; 4]| {
+        [
+            iv[0].wrapping_add(q[3]),
+            iv[1].wrapping_add(q[0]),
+            iv[2].wrapping_add(q[1]),
+            iv[3].wrapping_add(q[2]),
+        ]
+    };
+    let (state, state_p) = (output(iv, q), output(iv_p, q_p));
+    Outcome::Complete(core::array::from_fn(|i| state_p[i].wrapping_sub(state[i])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn published_pair_satisfies_conditions() {
+        let report = check_conditions(consts::published_iv(), &bytes_to_words(&consts::m1()));
+        assert!(report.is_satisfied(), "{}", report);
     }
-    {
-        macro_rules! F(
-            ($x:expr, $y:expr, $z:expr) => ($y ^ ($x | !$z));
-        );
-        macro_rules! T(
-            ($a:expr, $b:expr, $c:expr, $d:expr, $x:expr, $s:expr, $ac:expr) => ({
-                $a = add!(add!(add!($a, F!($b, $c, $d)), $x), $ac);
-                $a = rotate_left!($a, $s);
-                $a = add!($a, $b);
+
+    #[test]
+    fn admitted_ivs_follow_round1() {
+        let rng = fastrand::Rng::with_seed(1);
+        let mut admitted = 0;
+        while admitted < 20 {
+            let iv: [u32; 4] = core::array::from_fn(|_| rng.u32(..));
+            let iv_p = core::array::from_fn(|i| {
+                iv[i].wrapping_add(crate::task3::FIRST_BLOCK_OUTPUT_DIFF[i])
             });
-        );
-        const S1: u32 =  6;
-        const S2: u32 = 10;
-        const S3: u32 = 15;
-        const S4: u32 = 21;
-        T!(a, b, c, d, input[ 0], S1, 4096336452);
-        T!(d, a, b, c, input[ 7], S2, 1126891415);
-        T!(c, d, a, b, input[14], S3, 2878612391);
-        T!(b, c, d, a, input[ 5], S4, 4237533241);
-        T!(a, b, c, d, input[12], S1, 1700485571);
-        T!(d, a, b, c, input[ 3], S2, 2399980690);
-        T!(c, d, a, b, input[10], S3, 4293915773);
-        T!(b, c, d, a, input[ 1], S4, 2240044497);
-        T!(a, b, c, d, input[ 8], S1, 1873313359);
-        T!(d, a, b, c, input[15], S2, 4264355552);
-        T!(c, d, a, b, input[ 6], S3, 2734768916);
-        T!(b, c, d, a, input[13], S4, 1309151649);
-        T!(a, b, c, d, input[ 4], S1, 4149444226);
-        T!(d, a, b, c, input[11], S2, 3174756917);
-        T!(c, d, a, b, input[ 2], S3,  718787259);
-        T!(b, c, d, a, input[ 9], S4, 3951481745);
+            if !wang_second_block().admits_iv(wang_second_block_path(), iv, iv_p) {
+                continue;
+            }
+            admitted += 1;
+            let follows = (0..200).any(|_| {
+                let mut block = core::array::from_fn(|_| rng.u32(..));
+                wang_second_block().follow_round1(wang_second_block_path(), iv, &mut block)
+            });
+            assert!(follows, "{:x?}", iv);
+        }
     }
-    state[0] = add!(state[0], a);
-    state[1] = add!(state[1], b);
-    state[2] = add!(state[2], c);
-    state[3] = add!(state[3], d);
-}
 
+    #[test]
+    fn reports_violated_bits() {
+        let iv = consts::published_iv();
+        let mut block = bytes_to_words(&consts::m1());
+        // W15 only enters at step 15, where bit 9 rotates onto the free
+        // bit 31 of Q16; the first broken condition is Q17[4] = Q16[4].
+        block[15] ^= 0x100;
+        let report = check_conditions(iv, &block);
+        assert_eq!(report.first_violation(), Some(17));
+        assert_eq!((report.violations[0].t, report.violations[0].bit), (17, 3));
+    }
+
+    #[test]
+    fn published_first_block_satisfies_conditions() {
+        let iv = Context::new().state();
+        let block = bytes_to_words(&consts::m0());
+        let report = wang_first_block().check(wang_first_block_path(), iv, &block);
+        assert!(report.is_satisfied(), "{}", report);
+    }
+
+    #[test]
+    fn evaluate_aborts_at_first_deviation() {
+        let (iv, iv_p) = consts::published_ivs();
+        let block = bytes_to_words(&consts::m1());
+        let block_p = bytes_to_words(&consts::m1_p());
+        assert!(evaluate(iv, iv_p, &block, &block_p).is_collision());
+
+        let mut broken = block;
+        let mut broken_p = block_p;
+        broken[15] ^= 0x100;
+        broken_p[15] ^= 0x100;
+        assert_eq!(evaluate(iv, iv_p, &broken, &broken_p), Outcome::Abort(17));
+    }
+
+    #[test]
+    fn modification_reaches_round_two() {
+        let iv = consts::published_iv();
+        let rng = fastrand::Rng::with_seed(5);
+        let mut modified = 0;
+        for _ in 0..200 {
+            let mut block = [0u32; 16];
+            block.iter_mut().for_each(|word| *word = rng.u32(..));
+            let mut state = iv;
+            if transform_attack(&mut state, &mut block) {
+                modified += 1;
+                assert!(check_conditions(iv, &block).first_violation().is_none_or(|t| t > 20));
+            }
+        }
+        assert!(modified > 0);
+    }
+
+    #[test]
+    fn round_two_corrections_rarely_fail() {
+        let iv = consts::published_iv();
+        let (conditions, path) = (wang_second_block(), wang_second_block_path());
+        let rng = fastrand::Rng::with_seed(6);
+        let (mut followed, mut modified) = (0, 0);
+        while followed < 100 {
+            let mut block = [0u32; 16];
+            block.iter_mut().for_each(|word| *word = rng.u32(..));
+            if conditions.follow_round1(path, iv, &mut block) {
+                followed += 1;
+                modified += conditions.modify_round2(path, iv, &mut block) as usize;
+            }
+        }
+        // Random blocks would meet the rows of Q17 to Q20 about once in 2^10.
+        assert!(modified >= 20, "{}", modified);
+    }
+}
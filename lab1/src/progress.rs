@@ -0,0 +1,244 @@
+//! Progress reports of a running search.
+//!
+//! A search draws random base blocks (attempts); each one that message
+//! modification brings through `Q_20` yields a batch of candidates through
+//! its tunnels. The expected time to success assumes every condition after
+//! `Q_20` holds independently with probability 1/2, and that every step
+//! after `Q_20` rotates as the path needs with the chance it has for a
+//! random `T_t`. The latter also counts steps with no conditions, such as
+//! `Q_35`, which fails for about half the candidates. Neither is exact, so
+//! it is an estimate of the order of magnitude, not an exact time.
+//!
+//! Where candidates fail is tallied in a [`Histogram`] by the first step
+//! that broke its conditions or left the path. Candidates that get through
+//! every step and are still rejected are tallied apart.
+
+use core::fmt;
+use std::time::Duration;
+
+/// Counters of a search at one point in time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// Time since the search started.
+    pub elapsed: Duration,
+    pub attempts: u64,
+    pub candidates: u64,
+    /// `log2` of the probability that a candidate succeeds.
+    pub log2_probability: f64,
+}
+
+impl Progress {
+    /// Candidates per second so far.
+    pub fn rate(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.candidates as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Expected time until a candidate succeeds at the current rate. Since
+    /// candidates are independent, this does not shrink as time passes.
+    /// See the [module documentation](self) for how far to trust it.
+    pub fn expected(&self) -> Option<Duration> {
+        let rate = self.rate();
+        if rate > 0.0 {
+            Duration::try_from_secs_f64((-self.log2_probability).exp2() / rate).ok()
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}: {} attempts, {} candidates at {}/s",
+            Elapsed(self.elapsed),
+            Count(self.attempts as f64),
+            Count(self.candidates as f64),
+            Count(self.rate()),
+        )?;
+        match self.expected() {
+            Some(expected) => write!(
+                formatter,
+                ", expected time to success {}",
+                Elapsed(expected)
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Rejected candidates by the first step `Q_t`, `t` in `1..=64`, that
+/// broke its conditions or left the path, and those rejected after all 64
+/// steps.
+///
+/// Displayed as one line per step that rejected anything, with its share
+/// of the total; those rejected at the end are shown as `end`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    pub steps: [u64; 64],
+    /// Candidates that followed the path through `Q_64`, but whose output
+    /// difference was not the one sought or that the search turned down.
+    pub end: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            steps: [0; 64],
+            end: 0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Count a candidate that failed at `Q_t`.
+    #[inline]
+    pub fn record(&mut self, t: usize) {
+        self.steps[t - 1] += 1;
+    }
+
+    /// Count a candidate rejected after all 64 steps.
+    #[inline]
+    pub fn record_end(&mut self) {
+        self.end += 1;
+    }
+
+    /// Add the counts of `other`.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.steps.iter_mut().zip(other.steps.iter()) {
+            *count += other;
+        }
+        self.end += other.end;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.steps.iter().sum::<u64>() + self.end
+    }
+
+    /// A `step,rejected` header, one line per step and an `end` line.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("step,rejected\n");
+        for (i, count) in self.steps.iter().enumerate() {
+            csv.push_str(&format!("{},{}\n", i + 1, count));
+        }
+        csv.push_str(&format!("end,{}\n", self.end));
+        csv
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let total = self.total().max(1) as f64;
+        for (i, &count) in self
+            .steps
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+        {
+            let share = 100.0 * count as f64 / total;
+            writeln!(formatter, "Q{:<3} {:>14} {:>6.2}%", i + 1, count, share)?;
+        }
+        if self.end > 0 {
+            let share = 100.0 * self.end as f64 / total;
+            writeln!(formatter, "end  {:>14} {:>6.2}%", self.end, share)?;
+        }
+        Ok(())
+    }
+}
+
+/// A count with an SI suffix: `950`, `12.3k`, `4.6M`.
+struct Count(f64);
+
+impl fmt::Display for Count {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let mut value = self.0;
+        for suffix in ["", "k", "M", "G", "T"] {
+            if value < 1000.0 || suffix == "T" {
+                return if suffix.is_empty() {
+                    write!(formatter, "{:.0}", value)
+                } else {
+                    write!(formatter, "{:.1}{}", value, suffix)
+                };
+            }
+            value /= 1000.0;
+        }
+        Ok(())
+    }
+}
+
+/// A duration in its two largest units: `4.2s`, `3m07s`, `2h15m`, `3d04h`.
+struct Elapsed(Duration);
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.0.as_secs();
+        match seconds {
+            0..60 => write!(formatter, "{:.1}s", self.0.as_secs_f64()),
+            60..3600 => write!(formatter, "{}m{:02}s", seconds / 60, seconds % 60),
+            3600..86400 => write!(formatter, "{}h{:02}m", seconds / 3600, seconds / 60 % 60),
+            _ => write!(formatter, "{}d{:02}h", seconds / 86400, seconds / 3600 % 24),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_rate_and_expected_time() {
+        let progress = Progress {
+            elapsed: Duration::from_secs(4),
+            attempts: 1500,
+            candidates: 8_000_000,
+            log2_probability: -30.0,
+        };
+        assert_eq!(progress.rate(), 2e6);
+        assert_eq!(
+            progress.expected().unwrap().as_secs(),
+            (1 << 30) / 2_000_000
+        );
+        assert_eq!(
+            progress.to_string(),
+            "4.0s: 1.5k attempts, 8.0M candidates at 2.0M/s, expected time to success 8m56s"
+        );
+    }
+
+    #[test]
+    fn histogram_by_step() {
+        let mut histogram = Histogram::default();
+        histogram.record(21);
+        histogram.record(21);
+        let mut other = Histogram::default();
+        other.record(64);
+        other.record(1);
+        other.record_end();
+        other.record_end();
+        other.record_end();
+        histogram.merge(&other);
+        assert_eq!(histogram.total(), 7);
+        assert_eq!(
+            histogram.to_string(),
+            "Q1                1  14.29%\nQ21               2  28.57%\nQ64               1  14.29%\n\
+             end               3  42.86%\n"
+        );
+        let csv = histogram.to_csv();
+        assert_eq!(csv.lines().count(), 66);
+        assert_eq!(csv.lines().nth(21), Some("21,2"));
+        assert_eq!(csv.lines().last(), Some("end,3"));
+    }
+
+    #[test]
+    fn formats_durations() {
+        let format = |seconds| Elapsed(Duration::from_secs(seconds)).to_string();
+        assert_eq!(format(59), "59.0s");
+        assert_eq!(format(187), "3m07s");
+        assert_eq!(format(8100), "2h15m");
+        assert_eq!(format(273600), "3d04h");
+    }
+}
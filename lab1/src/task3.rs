@@ -1,8 +1,63 @@
-use super::md5_attack::{transform_attack};
-use super::md5::{Context, transform};
+use super::checkpoint::Checkpoint;
+use super::conditions::BitConditions;
+use super::consts;
+use super::differential::{DifferenceMismatch, DifferentialPath, MessageDifference};
+use super::md5_attack::{
+    evaluate_with, wang_first_block, wang_first_block_path, wang_second_block,
+    wang_second_block_path, Outcome,
+};
+use super::md5::{transform, Context, ROTATION};
+use super::progress::{Histogram, Progress};
+use super::tunnels::TunnelSet;
 
 use fastrand::{Rng};
-use std::thread;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::{fs, thread};
+use std::time::{Duration, Instant};
+
+/// Modular difference `IV' - IV` that the first block pair has to produce
+/// for Wang's second-block path: 2^31 in every word, plus 2^25 in b, c and d.
+pub const FIRST_BLOCK_OUTPUT_DIFF: [u32; 4] = [0x80000000, 0x82000000, 0x82000000, 0x82000000];
+
+/// Difference `m0' - m0` of Wang's first block: `+2^31` in words 4 and 14,
+/// `+2^15` in word 11.
+pub const FIRST_BLOCK_MESSAGE_DIFF: MessageDifference = MessageDifference::new([
+    0, 0, 0, 0, 1 << 31, 0, 0, 0, 0, 0, 0, 1 << 15, 0, 0, 1 << 31, 0,
+])
+.unwrap();
+
+/// Difference `m1' - m1` of Wang's second block: `+2^31` in words 4 and 14,
+/// `-2^15` in word 11.
+pub const SECOND_BLOCK_MESSAGE_DIFF: MessageDifference = MessageDifference::new([
+    0, 0, 0, 0, 1 << 31, 0, 0, 0, 0, 0, 0, -(1 << 15), 0, 0, 1 << 31, 0,
+])
+.unwrap();
+
+/// Compute the chaining values after the first blocks `m0` and `m0_p`.
+///
+/// Returns `None` if either message is not exactly one block, or if the
+/// resulting states do not differ by [`FIRST_BLOCK_OUTPUT_DIFF`].
+pub fn second_block_ivs(m0: &[u8], m0_p: &[u8]) -> Option<([u32; 4], [u32; 4])> {
+    if m0.len() != 64 || m0_p.len() != 64 {
+        return None;
+    }
+    let mut context = Context::new();
+    context.consume(m0);
+    let mut context_p = Context::new();
+    context_p.consume(m0_p);
+    let (iv, iv_p) = (context.state(), context_p.state());
+
+    let diff_ok = (0..4).all(|i| iv_p[i].wrapping_sub(iv[i]) == FIRST_BLOCK_OUTPUT_DIFF[i]);
+    if diff_ok {
+        Some((iv, iv_p))
+    } else {
+        None
+    }
+}
 
 fn rand_m1(m1: &mut [u32; 16], rng: &Rng) {
     for word in m1.iter_mut().take(16) {
@@ -10,74 +65,1178 @@ fn rand_m1(m1: &mut [u32; 16], rng: &Rng) {
     }
 }
 
-fn m0_init(context: &mut Context) {
-    context.count[0] = 0;
-    context.count[1] = 0;
+/// SplitMix64: a bijection on `u64` that scatters nearby inputs.
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Seed of worker `index` in a search started from `master`. Distinct
+/// workers get distinct seeds. The master is mixed before the index is
+/// combined with it, so neighbouring masters do not share worker seeds.
+pub fn thread_seed(master: u64, index: u64) -> u64 {
+    mix(mix(master) ^ index)
+}
+
+/// The random block a search with `seed` starts its `iteration`th attempt
+/// from. Each attempt has its own generator, so any of them can be
+/// replayed without the ones before it.
+fn base_block(seed: u64, iteration: u64) -> [u32; 16] {
+    let mut block = [0u32; 16];
+    rand_m1(&mut block, &Rng::with_seed(mix(seed ^ mix(iteration))));
+    block
+}
+
+/// A block pair found by a search, with what it takes to find it again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Found {
+    pub block: [u32; 16],
+    pub block_p: [u32; 16],
+    /// Seed of the search that found the pair.
+    pub seed: u64,
+    /// Attempt the pair was reached from; see [`search_second_block`].
+    pub iteration: u64,
+}
+
+/// The state of one worker of a search: the seed its attempts are drawn
+/// with, and the first attempt it has not finished.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Worker {
+    pub seed: u64,
+    pub next: u64,
+}
+
+/// What a multithreaded search may spend before giving up. A command that
+/// runs several searches, such as `collide`, spends them over all of them;
+/// see [`SearchConfig::spent`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Wall-clock time after which every worker stops.
+    pub timeout: Option<Duration>,
+    /// Attempts, counted over all workers, after which they stop.
+    pub attempts: Option<u64>,
+}
+
+/// Time and attempts spent searching.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Spent {
+    pub elapsed: Duration,
+    pub attempts: u64,
+}
+
+/// How a multithreaded search ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Search {
+    /// The first pair a worker found, if any.
+    pub found: Option<Found>,
+    /// What this search and the earlier ones of its command spent.
+    pub spent: Spent,
+}
+
+/// Interval between checkpoints of a search that has a checkpoint file.
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How a multithreaded search runs.
+#[derive(Clone, Debug)]
+pub struct SearchConfig {
+    pub threads: usize,
+    /// Seed the worker seeds are derived from; see [`thread_seed`].
+    pub master: u64,
+    pub limits: Limits,
+    /// Interval between progress reports on stderr. With `None`, neither
+    /// reports nor a final summary are printed.
+    pub progress: Option<Duration>,
+    /// CSV file the failure [`Histogram`] is written to when the search
+    /// ends, with `label` added to its name; see [`histogram_path`].
+    pub histogram: Option<PathBuf>,
+    /// Name of this search among those of one command, such as `first`.
+    pub label: Option<&'static str>,
+    /// File a [`Checkpoint`] is saved to when the search starts, every
+    /// [`CHECKPOINT_INTERVAL`], and when it stops without a pair.
+    pub checkpoint: Option<PathBuf>,
+    /// Checkpoint to carry on from. Its workers replace `threads` and
+    /// `master`.
+    pub resume: Option<Checkpoint>,
+    /// Stops the search once set, as a signal handler does.
+    pub interrupt: Option<Arc<AtomicBool>>,
+    /// Arguments of the command running the search, saved in checkpoints.
+    pub command: Vec<String>,
+    /// Chaining value the command searches from, saved in checkpoints so
+    /// that resuming can check it.
+    pub iv: Option<[u32; 4]>,
+    /// Pairs found by earlier searches of the same command, saved in
+    /// checkpoints.
+    pub earlier: Vec<Found>,
+    /// What earlier searches of the same command spent. It counts against
+    /// `limits` and is saved in checkpoints.
+    pub spent: Spent,
+}
+
+impl SearchConfig {
+    /// One worker per core, no limits, no progress reports and no
+    /// checkpoints.
+    pub fn new(master: u64) -> SearchConfig {
+        SearchConfig {
+            threads: num_cpus::get(),
+            master,
+            limits: Limits::default(),
+            progress: None,
+            histogram: None,
+            label: None,
+            checkpoint: None,
+            resume: None,
+            interrupt: None,
+            command: Vec::new(),
+            iv: None,
+            earlier: Vec::new(),
+            spent: Spent::default(),
+        }
+    }
+}
+
+/// State shared by the workers of a search: a stop flag, the attempts and
+/// candidates made so far, where they failed and how far each worker got.
+#[derive(Debug, Default)]
+pub struct Control {
+    stop: AtomicBool,
+    attempts: AtomicU64,
+    candidates: AtomicU64,
+    finished: Mutex<Finished>,
+    budget: Option<u64>,
+}
+
+/// What the finished attempts yielded, and the next attempt of each worker.
+/// Both are kept under one lock, so that a checkpoint sees them agree.
+#[derive(Clone, Debug, Default)]
+struct Finished {
+    tally: Tally,
+    workers: Vec<Worker>,
+}
+
+/// What one or more attempts yielded.
+#[derive(Clone, Copy, Debug, Default)]
+struct Tally {
+    candidates: u64,
+    /// Attempts that yielded no candidates.
+    unmodified: u64,
+    failures: Histogram,
+}
+
+impl Control {
+    /// A control that stops the workers after `budget` attempts, if given.
+    pub fn new(budget: Option<u64>) -> Control {
+        Control {
+            budget,
+            ..Control::default()
+        }
+    }
+
+    /// Ask every worker to stop.
+    #[inline]
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Attempts started so far.
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    /// Candidates evaluated so far, including those of attempts under way.
+    /// Workers add theirs in batches.
+    pub fn candidates(&self) -> u64 {
+        self.candidates.load(Ordering::Relaxed)
+    }
+
+    /// Finished attempts that message modification could not bring through
+    /// `Q_20`. They yield no candidates.
+    pub fn unmodified(&self) -> u64 {
+        self.finished.lock().unwrap().tally.unmodified
+    }
+
+    /// First failing steps of the candidates of finished attempts.
+    pub fn histogram(&self) -> Histogram {
+        self.finished.lock().unwrap().tally.failures
+    }
+
+    /// The state of every worker that has finished an attempt, or that the
+    /// search was started with.
+    pub fn workers(&self) -> Vec<Worker> {
+        self.finished.lock().unwrap().workers.clone()
+    }
+
+    /// Record that the worker with `seed` finished attempt `iteration`,
+    /// which yielded `tally`.
+    fn finish(&self, seed: u64, iteration: u64, tally: &Tally) {
+        let mut finished = self.finished.lock().unwrap();
+        finished.tally.candidates += tally.candidates;
+        finished.tally.unmodified += tally.unmodified;
+        finished.tally.failures.merge(&tally.failures);
+        let next = iteration + 1;
+        match finished.workers.iter_mut().find(|worker| worker.seed == seed) {
+            Some(worker) => worker.next = next,
+            None => finished.workers.push(Worker { seed, next }),
+        }
+    }
+
+    /// Give back an attempt that was claimed but not finished. What it
+    /// yielded so far is not recorded, as resuming makes it again.
+    fn abandon(&self) {
+        self.attempts.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Add a worker's candidates since its last report, and reset them.
+    fn report(&self, candidates: &mut u64) {
+        self.candidates.fetch_add(*candidates, Ordering::Relaxed);
+        *candidates = 0;
+    }
+
+    /// Claim the next attempt. Returns `false` once the search is stopped or
+    /// the budget is spent.
+    fn claim(&self) -> bool {
+        if self.is_stopped() {
+            return false;
+        }
+        let claimed = self.attempts.fetch_add(1, Ordering::Relaxed);
+        if self.budget.is_some_and(|budget| claimed >= budget) {
+            self.attempts.fetch_sub(1, Ordering::Relaxed);
+            self.stop();
+            return false;
+        }
+        true
+    }
+}
+
+/// Search from `iv` and `iv_p` for a block pair `(block, block + difference)`
+/// that follows `path`, whose chaining values then differ by `output`, and
+/// that `accept` takes.
+///
+/// Only the attempts in `iterations` are made, each from
+/// [`base_block`]`(seed, iteration)`, and only while `control` allows.
+/// Each finished attempt is recorded in `control`; one cut short by a stop
+/// is given back, so that resuming makes it again.
+#[allow(clippy::too_many_arguments)]
+fn search_block<F>(
+    conditions: &BitConditions,
+    path: &DifferentialPath,
+    iv: [u32; 4],
+    iv_p: [u32; 4],
+    difference: &MessageDifference,
+    output: [u32; 4],
+    seed: u64,
+    iterations: Range<u64>,
+    control: &Control,
+    accept: F,
+) -> Option<Found>
+where
+    F: Fn(&[u32; 16], &[u32; 16]) -> bool,
+{
+    // Every base block satisfying Q1..Q20 is followed by 2^n tunnel
+    // candidates that keep those steps intact.
+    let tunnels = TunnelSet::derive(conditions, path, iv, 20);
+    let difference = difference.modular();
 
-    context.state[0] = 0x52589324;
-    context.state[1] = 0x3093d7ca;
-    context.state[2] = 0x2a06dc54;
-    context.state[3] = 0x20c5be06;
-  }
+    let mut block_p = [0u32; 16];
+
+    for iteration in iterations {
+        if !control.claim() {
+            break;
+        }
+        let mut block = base_block(seed, iteration);
+        // Recorded only once the attempt is finished, so that a checkpoint
+        // never counts part of an attempt that resuming makes again.
+        let mut tally = Tally::default();
+
+        let conditions = &tunnels.conditions;
+        if !conditions.follow_round1(path, iv, &mut block)
+            || !conditions.modify_round2(path, iv, &mut block)
+        {
+            tally.unmodified = 1;
+            control.finish(seed, iteration, &tally);
+            continue;
+        }
+
+        // Set only when a pair is taken, unlike the value `visit` returns,
+        // which also ends the enumeration on a stop.
+        let mut accepted = None;
+        let mut unreported = 0u64;
+        tunnels.enumerate(iv, &mut block, |block| {
+            if control.is_stopped() {
+                return true;
+            }
+            tally.candidates += 1;
+            unreported += 1;
+            if unreported == 1 << 12 {
+                control.report(&mut unreported);
+            }
+            for i in 0..16 {
+                block_p[i] = block[i].wrapping_add(difference[i]);
+            }
+            match evaluate_with(conditions, path, iv, iv_p, block, &block_p) {
+                Outcome::Abort(t) => {
+                    tally.failures.record(t);
+                    false
+                }
+                Outcome::Complete(actual) => {
+                    if actual == output && accept(block, &block_p) {
+                        accepted = Some((*block, block_p));
+                    } else {
+                        tally.failures.record_end();
+                    }
+                    accepted.is_some()
+                }
+            }
+        });
+        control.report(&mut unreported);
+        // A pair in hand is returned even if the search was stopped while
+        // it was being checked.
+        if let Some((block, block_p)) = accepted {
+            control.finish(seed, iteration, &tally);
+            return Some(Found {
+                block,
+                block_p,
+                seed,
+                iteration,
+            });
+        }
+        if control.is_stopped() {
+            control.abandon();
+            break;
+        }
+        control.finish(seed, iteration, &tally);
+    }
+    None
+}
 
-fn m0_p_init(context: &mut Context) {
-    context.count[0] = 0;
-    context.count[1] = 0;
+/// Make the first-block attempts in `iterations` of a search with `seed`,
+/// and return the first pair from `iv` whose chaining values differ by
+/// [`FIRST_BLOCK_OUTPUT_DIFF`], ready for the second-block search.
+///
+/// The chaining values must also meet the conditions the second-block
+/// path puts on its IV, which only about one pair in 2^8 does.
+pub fn search_first_block(
+    iv: [u32; 4],
+    seed: u64,
+    iterations: Range<u64>,
+    control: &Control,
+) -> Option<Found> {
+    search_block(
+        wang_first_block(),
+        wang_first_block_path(),
+        iv,
+        iv,
+        &FIRST_BLOCK_MESSAGE_DIFF,
+        FIRST_BLOCK_OUTPUT_DIFF,
+        seed,
+        iterations,
+        control,
+        |m0, m0_p| {
+            let (mut next, mut next_p) = (iv, iv);
+            transform(&mut next, m0);
+            transform(&mut next_p, m0_p);
+            wang_second_block().admits_iv(wang_second_block_path(), next, next_p)
+        },
+    )
+}
 
-    context.state[0] = 0xd2589324;
-    context.state[1] = 0xb293d7ca;
-    context.state[2] = 0xac06dc54;
-    context.state[3] = 0xa2c5be06;
+/// Search for a first-block pair from `iv`; see [`search_first_block`].
+pub fn find_m0_m0_p(iv: [u32; 4], seed: u64) -> Found {
+    search_first_block(iv, seed, 0..u64::MAX, &Control::default()).expect("the search is unbounded")
 }
 
-fn find_m1_m1_p(seed: u64) -> ([u32; 16], [u32; 16]) {
-    let mut found = false;
+/// Make the second-block attempts in `iterations` of a search with `seed`,
+/// and return the first pair `(m1, m1 + difference)` from `iv` and `iv_p`
+/// whose chaining values collide.
+///
+/// Attempt `i` starts from a block drawn from its own generator, so a pair
+/// reported with seed `s` and iteration `i` is found again by searching
+/// `i..i + 1` with seed `s`. Fails before searching if `difference` is not
+/// the difference of the second-block path, since no pair could then
+/// follow the path.
+pub fn search_second_block(
+    iv: [u32; 4],
+    iv_p: [u32; 4],
+    difference: &MessageDifference,
+    seed: u64,
+    iterations: Range<u64>,
+    control: &Control,
+) -> Result<Option<Found>, DifferenceMismatch> {
+    let path = wang_second_block_path();
+    path.check_message(difference)?;
 
-    let mut m1= [0u32; 16];
-    let mut m1_p= [0u32; 16];
-    
-    let rng = fastrand::Rng::with_seed(seed);
-    let mut context1 = Context::new();
-    let mut context2 = Context::new();
+    Ok(search_block(
+        wang_second_block(),
+        path,
+        iv,
+        iv_p,
+        difference,
+        [0; 4],
+        seed,
+        iterations,
+        control,
+        |_, _| true,
+    ))
+}
 
-    while !found {
-        m0_init(&mut context1);
-        m0_p_init(&mut context2);
+/// Search for a second-block pair `(m1, m1 + difference)` from `iv` and
+/// `iv_p` whose chaining values collide.
+///
+/// `difference` is usually [`SECOND_BLOCK_MESSAGE_DIFF`]; see
+/// [`search_second_block`] for how it is checked.
+pub fn find_m1_m1_p(
+    iv: [u32; 4],
+    iv_p: [u32; 4],
+    difference: &MessageDifference,
+    seed: u64,
+) -> Result<Found, DifferenceMismatch> {
+    let found = search_second_block(iv, iv_p, difference, seed, 0..u64::MAX, &Control::default())?;
+    Ok(found.expect("the search is unbounded"))
+}
 
-        rand_m1(&mut m1, &rng);
+/// `log2` of the probability that a candidate following `path` through
+/// `Q_20` meets every condition in `rows`, taking each as a coin flip, and
+/// that the steps computing those rows rotate as `path` needs; see
+/// [`progress`](crate::progress) for what that assumes.
+fn log2_probability(
+    conditions: &BitConditions,
+    path: &DifferentialPath,
+    rows: RangeInclusive<isize>,
+) -> f64 {
+    rows.map(|t| {
+        let rotation = if t > 0 { log2_rotation(path, t) } else { 0.0 };
+        rotation - conditions.masks(t).count() as f64
+    })
+    .sum()
+}
 
-        transform_attack(&mut context1.state, &mut m1);
+/// `log2` of the probability that the rotation of the step computing `Q_t`
+/// turns the difference of `T` into the `ΔQ_t - ΔQ_{t-1}` that `path`
+/// needs. No bit condition covers this. For a uniformly random `T` it is
+/// the chance that adding the difference carries out of neither part the
+/// rotation moves; of the two differences of `T` that rotate onto the one
+/// needed, the likelier is taken.
+fn log2_rotation(path: &DifferentialPath, t: isize) -> f64 {
+    let s = ROTATION[t as usize - 1];
+    let needed = path.q(t).modular().wrapping_sub(path.q(t - 1).modular());
+    let keeps = |before: u32| {
+        let (low, high) = (before & (u32::MAX >> s), before >> (32 - s));
+        (1.0 - low as f64 / (1u64 << (32 - s)) as f64) * (1.0 - high as f64 / (1u64 << s) as f64)
+    };
+    keeps(needed.rotate_right(s)).max(keeps(needed.wrapping_neg().rotate_right(s))).log2()
+}
+
+/// Run `search(worker, control)` on `config.threads` workers, seeded by
+/// [`thread_seed`] from `config.master`, and return the first pair any of
+/// them finds, with what was spent. Each worker searches from attempt
+/// `worker.next` on.
+///
+/// Once a worker succeeds, the timeout passes, the attempt budget is spent
+/// or `config.interrupt` is set, every worker is stopped and joined. If
+/// `config.progress` is set, a [`Progress`] line goes to stderr at that
+/// interval, and once more at the end followed by the failure
+/// [`Histogram`]; `log2_probability` is the chance of a single candidate.
+/// The histogram is also written to `config.histogram`, if given.
+///
+/// With `config.checkpoint`, the state of the search is saved there when
+/// it starts, so that pairs in `config.earlier` are kept at once, then
+/// periodically and when it stops without a pair. A search resumed from
+/// `config.resume` makes the attempts the saved one had left. The timeout
+/// and attempt budget count what was spent before, by this search and by
+/// the earlier ones in `config.spent`.
+pub fn parallel_search<F>(config: &SearchConfig, log2_probability: f64, search: F) -> Search
+where
+    F: Fn(Worker, &Control) -> Option<Found> + Sync,
+{
+    let resume = config.resume.as_ref();
+    let spent = resume.map_or(config.spent, |checkpoint| checkpoint.spent);
+    let workers = match resume {
+        Some(checkpoint) => checkpoint.workers.clone(),
+        None => (0..config.threads as u64)
+            .map(|n| Worker {
+                seed: thread_seed(config.master, n),
+                next: 0,
+            })
+            .collect(),
+    };
+    let control = Control {
+        attempts: AtomicU64::new(resume.map_or(0, |checkpoint| checkpoint.attempts)),
+        candidates: AtomicU64::new(resume.map_or(0, |checkpoint| checkpoint.candidates)),
+        finished: Mutex::new(Finished {
+            tally: resume.map_or_else(Tally::default, |checkpoint| Tally {
+                candidates: checkpoint.candidates,
+                unmodified: checkpoint.unmodified,
+                failures: checkpoint.failures,
+            }),
+            workers: workers.clone(),
+        }),
+        ..Control::new(config.limits.attempts.map(|limit| limit.saturating_sub(spent.attempts)))
+    };
+    let before = resume.map_or(Duration::ZERO, |checkpoint| checkpoint.elapsed);
+    let start = Instant::now();
+    let progress = || Progress {
+        elapsed: before + start.elapsed(),
+        attempts: control.attempts(),
+        candidates: control.candidates(),
+        log2_probability,
+    };
+    let save = || {
+        let Some(path) = &config.checkpoint else {
+            return;
+        };
+        let Finished { tally, workers } = control.finished.lock().unwrap().clone();
+        let checkpoint = Checkpoint {
+            command: config.command.clone(),
+            master: config.master,
+            iv: config.iv,
+            limits: config.limits,
+            histogram: config.histogram.clone(),
+            elapsed: before + start.elapsed(),
+            // Attempts under way are left out, as they are made again.
+            attempts: workers.iter().map(|worker| worker.next).sum(),
+            candidates: tally.candidates,
+            unmodified: tally.unmodified,
+            failures: tally.failures,
+            earlier: config.earlier.clone(),
+            spent,
+            workers,
+        };
+        if let Err(error) = checkpoint.save(path) {
+            eprintln!("cannot write {}: {}", path.display(), error);
+        }
+    };
 
-        m1_p.copy_from_slice(&m1);
-        m1_p[4]  = m1_p[4].wrapping_add(0x80);
-        m1_p[11] = m1_p[11].wrapping_sub(0x20);
-        m1_p[14] = m1_p[14].wrapping_add(0x80);
+    save();
+    let (sender, receiver) = mpsc::channel();
+    let found = thread::scope(|scope| {
+        for worker in workers {
+            let (sender, control, search) = (sender.clone(), &control, &search);
+            scope.spawn(move || {
+                if let Some(found) = search(worker, control) {
+                    // The receiver is gone once another worker succeeded.
+                    let _ = sender.send(found);
+                }
+            });
+        }
+        drop(sender);
 
-        transform(&mut context2.state, &m1_p);        
+        let (mut next_progress, mut next_checkpoint) = (config.progress, CHECKPOINT_INTERVAL);
+        let found = loop {
+            match receiver.recv_timeout(Duration::from_millis(50)) {
+                Ok(found) => break Some(found),
+                // Every worker gave up.
+                Err(RecvTimeoutError::Disconnected) => break None,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            let timed_out = config
+                .limits
+                .timeout
+                .is_some_and(|timeout| spent.elapsed + before + start.elapsed() >= timeout);
+            let interrupted = config
+                .interrupt
+                .as_ref()
+                .is_some_and(|interrupt| interrupt.load(Ordering::Relaxed));
+            if timed_out || interrupted {
+                break None;
+            }
+            if let (Some(next), Some(interval)) = (&mut next_progress, config.progress) {
+                if start.elapsed() >= *next {
+                    eprintln!("{}", progress());
+                    *next += interval;
+                }
+            }
+            if start.elapsed() >= next_checkpoint {
+                save();
+                next_checkpoint += CHECKPOINT_INTERVAL;
+            }
+        };
+        control.stop();
+        found
+    });
+    // A worker may have found a pair while the search was being stopped.
+    let found = found.or_else(|| receiver.try_recv().ok());
+    if found.is_none() {
+        save();
+    }
+    let histogram = control.histogram();
+    if config.progress.is_some() {
+        let interrupted = config
+            .interrupt
+            .as_ref()
+            .is_some_and(|interrupt| interrupt.load(Ordering::Relaxed));
+        let outcome = match (&found, interrupted) {
+            (Some(_), _) => "found after",
+            (None, true) => "interrupted after",
+            (None, false) => "gave up after",
+        };
+        eprintln!("{} {}", outcome, progress());
+        eprintln!("message modification failed on {} attempts", control.unmodified());
+        eprint!("first failing step of the candidates:\n{}", histogram);
+    }
+    if let Some(path) = &config.histogram {
+        let path = histogram_path(path, config.label);
+        if let Err(error) = fs::write(&path, histogram.to_csv()) {
+            eprintln!("cannot write {}: {}", path.display(), error);
+        }
+    }
+    Search {
+        found,
+        spent: Spent {
+            elapsed: spent.elapsed + before + start.elapsed(),
+            attempts: spent.attempts + control.attempts(),
+        },
+    }
+}
 
-        found = context1.state == context2.state;
+/// `path` with `-label` added before its extension, if there is a label:
+/// `failures.csv` becomes `failures-first.csv`.
+pub fn histogram_path(path: &Path, label: Option<&str>) -> PathBuf {
+    let Some(label) = label else {
+        return path.to_path_buf();
+    };
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push("-");
+    name.push(label);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
     }
+    path.with_file_name(name)
+}
 
-    println!("m1: {:x?}", m1);
-    println!("m1_p: {:x?}", m1_p);
+/// [`search_first_block`] on several workers; see [`parallel_search`].
+pub fn parallel_find_m0_m0_p(iv: [u32; 4], config: &SearchConfig) -> Search {
+    let log2_probability = log2_probability(wang_first_block(), wang_first_block_path(), 21..=64)
+        + log2_probability(wang_second_block(), wang_second_block_path(), -3..=0);
+    parallel_search(config, log2_probability, |worker, control| {
+        search_first_block(iv, worker.seed, worker.next..u64::MAX, control)
+    })
+}
+
+/// [`search_second_block`] on several workers; see [`parallel_search`].
+///
+/// `difference` is checked before any worker starts.
+pub fn parallel_find_m1_m1_p(
+    iv: [u32; 4],
+    iv_p: [u32; 4],
+    difference: &MessageDifference,
+    config: &SearchConfig,
+) -> Result<Search, DifferenceMismatch> {
+    wang_second_block_path().check_message(difference)?;
+    let log2_probability = log2_probability(wang_second_block(), wang_second_block_path(), 21..=64);
+    Ok(parallel_search(config, log2_probability, |worker, control| {
+        search_second_block(iv, iv_p, difference, worker.seed, worker.next..u64::MAX, control)
+            .expect("the difference was checked")
+    }))
+}
 
-    (m1, m1_p)
+/// Search for second blocks after the published first blocks as `config`
+/// says, and return the pair found within its limits.
+pub fn multi_thread_find_m1_m1_p(config: &SearchConfig) -> Option<Found> {
+    let (iv, iv_p) = second_block_ivs(&consts::m0(), &consts::m0_p())
+        .expect("the published first blocks yield the second-block IV difference");
+    parallel_find_m1_m1_p(iv, iv_p, &SECOND_BLOCK_MESSAGE_DIFF, config)
+        .expect("the second-block difference is the one of its path")
+        .found
 }
 
-pub fn multi_thread_find_m1_m1_p() {
-    let num = num_cpus::get();
-    let items = vec![0; num];
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::derivation::derive_conditions;
+    use crate::differential::differential_trace;
+    use crate::md5::bytes_to_words;
+
+    /// A file in the temporary directory, unique to this test process.
+    fn temp(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("lab1-{}-{}", std::process::id(), name))
+    }
+
+    /// Search `iterations` with no conditions and no difference, where
+    /// every candidate is a pair, and hand each one to `accept`.
+    fn search_trivial<F>(control: &Control, iterations: Range<u64>, accept: F) -> Option<Found>
+    where
+        F: Fn(&[u32; 16], &[u32; 16]) -> bool,
+    {
+        let iv = Context::new().state();
+        let block = [0u32; 16];
+        let path = DifferentialPath::from_trace(&differential_trace(iv, &block, iv, &block));
+        let difference = MessageDifference::default();
+        let conditions = BitConditions::default();
+        search_block(&conditions, &path, iv, iv, &difference, [0; 4], 2, iterations, control, accept)
+    }
+
+    #[test]
+    fn ivs_from_consts() {
+        let (iv, iv_p) = consts::published_ivs();
+        assert_eq!(iv, [0x52589324, 0x3093d7ca, 0x2a06dc54, 0x20c5be06]);
+        assert_eq!(iv_p, [0xd2589324, 0xb293d7ca, 0xac06dc54, 0xa2c5be06]);
+    }
+
+    #[test]
+    fn first_block_difference_matches_path() {
+        assert_eq!(wang_first_block_path().check_message(&FIRST_BLOCK_MESSAGE_DIFF), Ok(()));
+    }
+
+    #[test]
+    fn second_block_difference_matches_published_pair() {
+        let m1 = bytes_to_words(&consts::m1());
+        let m1_p = bytes_to_words(&consts::m1_p());
+        assert_eq!(SECOND_BLOCK_MESSAGE_DIFF.apply(&m1), m1_p);
+        assert_eq!(wang_second_block_path().check_message(&SECOND_BLOCK_MESSAGE_DIFF), Ok(()));
+    }
+
+    #[test]
+    fn rejects_difference_off_the_path() {
+        let (iv, iv_p) = consts::published_ivs();
+        // The difference read big-endian instead of little-endian.
+        let mut words = [0; 16];
+        words[4] = 0x80;
+        words[11] = -0x20;
+        words[14] = 0x80;
+        let error = find_m1_m1_p(iv, iv_p, &MessageDifference::new(words).unwrap(), 0).unwrap_err();
+        assert_eq!((error.word, error.expected, error.actual), (4, 0x80000000, 0x80));
+    }
+
+    #[test]
+    fn published_first_block_admits_second_block() {
+        let (iv, iv_p) = consts::published_ivs();
+        assert!(wang_second_block().admits_iv(wang_second_block_path(), iv, iv_p));
+        assert!(!wang_second_block().admits_iv(wang_second_block_path(), iv_p, iv));
+    }
+
+    #[test]
+    fn thread_seeds_are_distinct() {
+        let seeds: Vec<u64> = (0..64).map(|n| thread_seed(7, n)).collect();
+        for (i, seed) in seeds.iter().enumerate() {
+            assert!(!seeds[..i].contains(seed));
+        }
+        assert_eq!(thread_seed(7, 3), seeds[3]);
+        assert_ne!(thread_seed(8, 3), seeds[3]);
+
+        // Searches with consecutive master seeds do not repeat each other.
+        let neighbours: Vec<u64> = (6..=8)
+            .flat_map(|master| (0..64).map(move |n| thread_seed(master, n)))
+            .collect();
+        for (i, seed) in neighbours.iter().enumerate() {
+            assert!(!neighbours[..i].contains(seed));
+        }
+    }
+
+    #[test]
+    fn attempts_replay_alone() {
+        assert_eq!(base_block(5, 1000), base_block(5, 1000));
+        assert_ne!(base_block(5, 1000), base_block(5, 1001));
+        assert_ne!(base_block(5, 1000), base_block(6, 1000));
+    }
+
+    #[test]
+    fn parallel_search_respects_budget() {
+        let (iv, iv_p) = consts::published_ivs();
+        let config = SearchConfig {
+            threads: 2,
+            limits: Limits {
+                timeout: None,
+                attempts: Some(10),
+            },
+            ..SearchConfig::new(1)
+        };
+        let found = parallel_find_m1_m1_p(iv, iv_p, &SECOND_BLOCK_MESSAGE_DIFF, &config).unwrap().found;
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn histogram_records_first_failures() {
+        let (iv, iv_p) = consts::published_ivs();
+        let control = Control::new(Some(4));
+        let found = search_second_block(iv, iv_p, &SECOND_BLOCK_MESSAGE_DIFF, 3, 0..4, &control);
+        assert_eq!(found, Ok(None));
+        // Every candidate was rejected somewhere; attempts that yielded none
+        // are counted apart.
+        let histogram = control.histogram();
+        assert_eq!(histogram.total(), control.candidates());
+        assert!(histogram.total() > 0);
+        assert!(control.unmodified() > 0 && control.unmodified() < 4);
+    }
+
+    #[test]
+    fn limits_count_earlier_searches() {
+        let (iv, iv_p) = consts::published_ivs();
+        let config = SearchConfig {
+            threads: 1,
+            limits: Limits {
+                timeout: None,
+                attempts: Some(12),
+            },
+            spent: Spent {
+                elapsed: Duration::ZERO,
+                attempts: 10,
+            },
+            ..SearchConfig::new(1)
+        };
+        let search = parallel_find_m1_m1_p(iv, iv_p, &SECOND_BLOCK_MESSAGE_DIFF, &config).unwrap();
+        assert_eq!(search.found, None);
+        assert_eq!(search.spent.attempts, 12);
+
+        // Earlier searches took all the time there was.
+        let config = SearchConfig {
+            threads: 1,
+            limits: Limits {
+                timeout: Some(Duration::from_secs(3600)),
+                attempts: None,
+            },
+            spent: Spent {
+                elapsed: Duration::from_secs(3600),
+                attempts: 0,
+            },
+            ..SearchConfig::new(1)
+        };
+        let start = Instant::now();
+        let search = parallel_search(&config, -1.0, |_, control| {
+            while !control.is_stopped() {
+                thread::yield_now();
+            }
+            None
+        });
+        assert_eq!(search.found, None);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(search.spent.elapsed >= Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn parallel_search_stops_on_timeout() {
+        let config = SearchConfig {
+            threads: 2,
+            limits: Limits {
+                timeout: Some(Duration::from_millis(50)),
+                attempts: None,
+            },
+            ..SearchConfig::new(1)
+        };
+        let start = Instant::now();
+        let found = parallel_search(&config, -1.0, |_, control| {
+            while !control.is_stopped() {
+                thread::yield_now();
+            }
+            None
+        }).found;
+        assert_eq!(found, None);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn parallel_search_returns_first_success() {
+        let config = SearchConfig {
+            threads: 4,
+            ..SearchConfig::new(9)
+        };
+        let found = parallel_search(&config, -1.0, |worker, control| {
+            if worker.seed == thread_seed(9, 2) {
+                Some(Found {
+                    block: [2; 16],
+                    block_p: [2; 16],
+                    seed: worker.seed,
+                    iteration: worker.next,
+                })
+            } else {
+                while !control.is_stopped() {
+                    thread::yield_now();
+                }
+                None
+            }
+        }).found;
+        assert_eq!(found.map(|found| found.seed), Some(thread_seed(9, 2)));
+    }
+
+    #[test]
+    fn resumed_search_continues_where_it_stopped() {
+        let (iv, iv_p) = consts::published_ivs();
+        let (path, other) = (temp("stopped.checkpoint"), temp("whole.checkpoint"));
+        let run = |attempts, checkpoint: Option<&PathBuf>, resume| {
+            let config = SearchConfig {
+                threads: 1,
+                limits: Limits {
+                    timeout: None,
+                    attempts: Some(attempts),
+                },
+                checkpoint: checkpoint.cloned(),
+                resume,
+                ..SearchConfig::new(4)
+            };
+            parallel_find_m1_m1_p(iv, iv_p, &SECOND_BLOCK_MESSAGE_DIFF, &config).unwrap().found
+        };
+
+        assert_eq!(run(10, Some(&path), None), None);
+        let stopped: Checkpoint = fs::read_to_string(&path).unwrap().parse().unwrap();
+        assert_eq!(stopped.attempts, 10);
+        assert_eq!(stopped.workers, [Worker { seed: thread_seed(4, 0), next: 10 }]);
+
+        assert_eq!(run(20, Some(&path), Some(stopped)), None);
+        let resumed: Checkpoint = fs::read_to_string(&path).unwrap().parse().unwrap();
+        assert_eq!(run(20, Some(&other), None), None);
+        let whole: Checkpoint = fs::read_to_string(&other).unwrap().parse().unwrap();
+        fs::remove_file(path).unwrap();
+        fs::remove_file(other).unwrap();
+
+        assert_eq!(resumed.attempts, 20);
+        assert_eq!(resumed.workers, whole.workers);
+        assert_eq!(resumed.candidates, whole.candidates);
+        assert_eq!(resumed.failures, whole.failures);
+    }
+
+    #[test]
+    fn search_stopped_mid_attempt_resumes_exactly() {
+        let (iv, iv_p) = consts::published_ivs();
+        let (path, other) = (temp("cut.checkpoint"), temp("budget.checkpoint"));
+        let config = |checkpoint: &PathBuf, resume| SearchConfig {
+            threads: 1,
+            limits: Limits {
+                timeout: None,
+                attempts: Some(20),
+            },
+            checkpoint: Some(checkpoint.clone()),
+            resume,
+            ..SearchConfig::new(6)
+        };
+        let saved = |checkpoint: &PathBuf| -> Checkpoint {
+            fs::read_to_string(checkpoint).unwrap().parse().unwrap()
+        };
 
-    let threads: Vec<_> = items
-        .into_iter()
-        .map(|n| {
-            thread::spawn(move || {
-                find_m1_m1_p(n);
+        // The search is stopped once the first attempt with candidates has
+        // reported some, long before it has made them all.
+        let found = parallel_search(&config(&path, None), -1.0, |worker, control| {
+            thread::scope(|scope| {
+                scope.spawn(|| {
+                    while control.candidates() == 0 {
+                        thread::yield_now();
+                    }
+                    control.stop();
+                });
+                let (seed, iterations) = (worker.seed, worker.next..u64::MAX);
+                let difference = &SECOND_BLOCK_MESSAGE_DIFF;
+                search_second_block(iv, iv_p, difference, seed, iterations, control).unwrap()
             })
-        })
-        .collect();
+        }).found;
+        assert_eq!(found, None);
+        let stopped = saved(&path);
+        assert_eq!((stopped.attempts, stopped.candidates), (stopped.unmodified, 0));
+
+        let resume = config(&path, Some(stopped));
+        let search = parallel_find_m1_m1_p(iv, iv_p, &SECOND_BLOCK_MESSAGE_DIFF, &resume).unwrap();
+        assert_eq!(search.found, None);
+        let resumed = saved(&path);
+        let whole = config(&other, None);
+        let search = parallel_find_m1_m1_p(iv, iv_p, &SECOND_BLOCK_MESSAGE_DIFF, &whole).unwrap();
+        assert_eq!(search.found, None);
+        let whole = saved(&other);
+        fs::remove_file(path).unwrap();
+        fs::remove_file(other).unwrap();
+
+        assert_eq!(resumed.attempts, 20);
+        assert_eq!(resumed.candidates, whole.candidates);
+        assert_eq!(resumed.unmodified, whole.unmodified);
+        assert_eq!(resumed.failures, whole.failures);
+    }
+
+    #[test]
+    fn parallel_search_saves_earlier_pairs_at_once() {
+        let path = temp("earlier");
+        let earlier = Found {
+            block: [3; 16],
+            block_p: [4; 16],
+            seed: 5,
+            iteration: 6,
+        };
+        let config = SearchConfig {
+            threads: 1,
+            checkpoint: Some(path.clone()),
+            iv: Some([7; 4]),
+            earlier: vec![earlier],
+            ..SearchConfig::new(1)
+        };
+        parallel_search(&config, -1.0, |_, _| {
+            let saved: Checkpoint = fs::read_to_string(&path).unwrap().parse().unwrap();
+            assert_eq!((saved.iv, saved.earlier), (Some([7; 4]), vec![earlier]));
+            None
+        });
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn stopped_search_gives_back_unfinished_attempt() {
+        let (iv, iv_p) = consts::published_ivs();
+        let control = Control::new(None);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(100));
+                control.stop();
+            });
+            let found =
+                search_second_block(iv, iv_p, &SECOND_BLOCK_MESSAGE_DIFF, 8, 0..u64::MAX, &control);
+            assert_eq!(found, Ok(None));
+        });
+        let next = control.workers().first().map_or(0, |worker| worker.next);
+        assert_eq!(control.attempts(), next);
+    }
+
+    #[test]
+    fn found_pair_is_found_again_from_its_attempt() {
+        // A second-block pair takes minutes to find, so the pair comes from
+        // a search with no conditions. Its tunnel changes W_8, so taking one
+        // W_8 in 1024 makes the pair a later candidate of its attempt.
+        let accept = |block: &[u32; 16], _: &[u32; 16]| block[8].is_multiple_of(1024);
+        let found = search_trivial(&Control::default(), 5..u64::MAX, accept).unwrap();
+        let iterations = found.iteration..found.iteration + 1;
+        assert_eq!(search_trivial(&Control::default(), iterations, accept), Some(found));
+
+        // Attempts of the second-block search replay alone as well.
+        let (iv, iv_p) = consts::published_ivs();
+        let difference = &SECOND_BLOCK_MESSAGE_DIFF;
+        let whole = Control::default();
+        assert_eq!(search_second_block(iv, iv_p, difference, 3, 0..4, &whole), Ok(None));
+        let alone = Control::default();
+        for i in (0..4).rev() {
+            assert_eq!(search_second_block(iv, iv_p, difference, 3, i..i + 1, &alone), Ok(None));
+        }
+        assert_eq!(alone.candidates(), whole.candidates());
+        assert_eq!(alone.histogram(), whole.histogram());
+    }
+
+    #[test]
+    fn keeps_pair_found_as_budget_runs_out() {
+        let control = Control::new(Some(1));
+        // Another worker spends the last attempt while this pair is checked.
+        let found = search_trivial(&control, 0..u64::MAX, |_, _| !control.claim());
+        assert!(control.is_stopped());
+        assert_eq!(found.map(|found| found.iteration), Some(0));
+    }
 
-    for handle in threads {
-        handle.join().unwrap()
+    #[test]
+    fn search_on_derived_conditions_yields_candidates() {
+        // Conditions derived from the path of the published pair alone.
+        let iv = Context::new().state();
+        let path = wang_first_block_path();
+        let conditions = derive_conditions(path).unwrap();
+        let control = Control::new(Some(60));
+        let found = search_block(
+            &conditions,
+            path,
+            iv,
+            iv,
+            &FIRST_BLOCK_MESSAGE_DIFF,
+            FIRST_BLOCK_OUTPUT_DIFF,
+            11,
+            0..60,
+            &control,
+            |_, _| false,
+        );
+        assert_eq!(found, None);
+        assert!(control.unmodified() < 60);
+        assert!(control.candidates() > 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn first_block_search_from_random_iv_stays_on_path() {
+        let rng = Rng::with_seed(16);
+        let iv = [rng.u32(..), rng.u32(..), rng.u32(..), rng.u32(..)];
+        assert!(wang_first_block().admits_iv(wang_first_block_path(), iv, iv));
+        let control = Control::new(Some(3));
+        assert_eq!(search_first_block(iv, 0, 0..3, &control), None);
+        assert!(control.candidates() > 0);
+        // Every candidate follows the path through Q20 and fails later, if
+        // at all.
+        let histogram = control.histogram();
+        assert_eq!(histogram.steps[..20].iter().sum::<u64>(), 0);
+        assert_eq!(histogram.total(), control.candidates());
+    }
+
+    #[test]
+    fn histogram_records_candidates_rejected_at_the_end() {
+        let control = Control::new(Some(1));
+        let offered = std::cell::Cell::new(0);
+        let found = search_trivial(&control, 0..u64::MAX, |_, _| {
+            offered.set(offered.get() + 1);
+            offered.get() == 3
+        });
+        assert!(found.is_some());
+        let histogram = control.histogram();
+        assert_eq!(histogram.end, 2);
+        assert_eq!(histogram.total(), control.candidates() - 1);
+    }
+
+    #[test]
+    fn parallel_search_keeps_pair_found_while_stopping() {
+        let config = SearchConfig {
+            threads: 1,
+            interrupt: Some(Arc::new(AtomicBool::new(true))),
+            ..SearchConfig::new(1)
+        };
+        let found = parallel_search(&config, -1.0, |worker, control| {
+            while !control.is_stopped() {
+                thread::yield_now();
+            }
+            Some(Found {
+                block: [1; 16],
+                block_p: [1; 16],
+                seed: worker.seed,
+                iteration: worker.next,
+            })
+        }).found;
+        assert_eq!(found.map(|found| found.block), Some([1; 16]));
+    }
+
+    #[test]
+    fn parallel_search_stops_on_interrupt() {
+        let config = SearchConfig {
+            threads: 2,
+            interrupt: Some(Arc::new(AtomicBool::new(true))),
+            ..SearchConfig::new(1)
+        };
+        let found = parallel_search(&config, -1.0, |_, control| {
+            while !control.is_stopped() {
+                thread::yield_now();
+            }
+            None
+        }).found;
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn second_block_probability_counts_late_conditions() {
+        let log2 = log2_probability(wang_second_block(), wang_second_block_path(), 21..=64);
+        assert!((-40.0..-20.0).contains(&log2), "{}", log2);
+        // The first block can start from any IV.
+        assert_eq!(log2_probability(wang_first_block(), wang_first_block_path(), -3..=0), 0.0);
+    }
+
+    #[test]
+    fn probability_counts_rotations_of_unconditioned_steps() {
+        let path = wang_second_block_path();
+        // Q35 has no bit condition, but its difference survives the
+        // rotation only if T_34 does not carry into bit 16.
+        assert!(wang_second_block().masks(35).is_empty());
+        assert_eq!(log2_rotation(path, 35), -1.0);
+        assert_eq!(log2_probability(wang_second_block(), path, 35..=35), -1.0);
+        // A step that adds no difference always rotates as needed.
+        assert_eq!(log2_rotation(path, 40), 0.0);
+    }
+
+    #[test]
+    fn labels_histogram_paths() {
+        let path = Path::new("out/failures.csv");
+        assert_eq!(histogram_path(path, None), path);
+        assert_eq!(histogram_path(path, Some("first")), Path::new("out/failures-first.csv"));
+        assert_eq!(histogram_path(Path::new("failures"), Some("second")), Path::new("failures-second"));
+    }
+
+    #[test]
+    fn ivs_reject_wrong_difference() {
+        assert!(second_block_ivs(&consts::m0(), &consts::m0()).is_none());
+    }
+}
@@ -0,0 +1,340 @@
+//! Klima tunnels for the second-block search.
+//!
+//! Flipping bit `j` of a first-round `Q_i` and recomputing `W_{i-1}` to
+//! `W_{i+3}` leaves every other `Q_1` to `Q_16` unchanged. If `Q_i[j]` is
+//! free, `W_{i+1}` is untouched when the table forces `Q_{i+1}[j] = 0`, since
+//! `F` then ignores its second input at that bit, and `W_{i+2}` is untouched
+//! when it forces `Q_{i+2}[j] = 1`. The steps of round 2 that only use
+//! untouched words keep their values, so a base block that satisfies the
+//! table up to such a step yields `2^n` blocks that do too.
+
+use super::conditions::{BitConditions, Condition};
+use super::differential::DifferentialPath;
+use super::md5::{states, step_inverse, WORD_INDEX};
+
+/// Random blocks used to estimate how often the first round can be fixed.
+const SAMPLES: usize = 256;
+
+/// Most tunnel bits a [`TunnelSet`] holds. Enumerating more than `2^32`
+/// candidates from one base block is never worth it, and the count must
+/// fit the `u64` that [`TunnelSet::enumerate`] walks.
+pub const MAX_STRENGTH: u32 = 32;
+
+/// A set of bits of one `Q_i` that can be flipped independently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tunnel {
+    /// The first-round state word, `1..=16`.
+    pub q: usize,
+    /// Bits of `Q_q` in the tunnel.
+    pub mask: u32,
+    /// Message words changed by the tunnel, one bit per word.
+    pub words: u16,
+    /// Last `Q_t` that no bit of the tunnel changes.
+    pub preserves: usize,
+}
+
+impl Tunnel {
+    /// Number of bits in the tunnel.
+    #[inline]
+    pub fn strength(&self) -> u32 {
+        self.mask.count_ones()
+    }
+
+    /// Flip `bits` of `Q_q` in `block`, given its first-round states `q`
+    /// (`Q_{-3}` to `Q_16`, as returned by [`states`]). Both are
+    /// updated.
+    pub fn apply(&self, q: &mut [u32; 20], block: &mut [u32; 16], bits: u32) {
+        debug_assert_eq!(bits & !self.mask, 0);
+        q[self.q + 3] ^= bits;
+        for t in self.q - 1..(self.q + 4).min(16) {
+            let window = [q[t + 3], q[t + 2], q[t + 1], q[t]];
+            block[t] = step_inverse(t, window, q[t + 4]);
+        }
+    }
+}
+
+/// Tunnels together with the conditions that open them.
+#[derive(Clone)]
+pub struct TunnelSet {
+    /// The original conditions plus the tunnel conditions.
+    pub conditions: BitConditions,
+    /// Tunnels, best preserved first.
+    pub tunnels: Vec<Tunnel>,
+}
+
+impl TunnelSet {
+    /// Derive the tunnels of `conditions` that preserve at least `Q_1` to
+    /// `Q_preserve` of blocks hashed from `iv` along `path`.
+    ///
+    /// A free bit `Q_i[j]` joins a tunnel if the words it changes are not
+    /// used in round 2 before step `preserve`. Where that needs
+    /// `Q_{i+1}[j] = 0` or `Q_{i+2}[j] = 1` and the bit is free and outside
+    /// every tunnel, the condition is added. Fixing too many bits of a row
+    /// can leave no value of `T_t` that rotates as the path needs, so an
+    /// added condition is kept only if
+    /// [`follow_round1`](BitConditions::follow_round1) still succeeds at
+    /// least half as often as without any. Bits of `Q_i` are grouped by the
+    /// words they change. Bits beyond [`MAX_STRENGTH`] are left out, those
+    /// of the best preserved `Q_i` first.
+    ///
+    /// The partner message has to keep its states too. Its boolean functions
+    /// only ignore `Q_i[j]` as the first message's do if `Q_{i-2}` to
+    /// `Q_{i+2}` have no difference at bit `j`, so other bits are skipped.
+    /// The rotation at step `i` can still carry the partner's difference
+    /// elsewhere, so a bit is only taken if flipping it leaves every other
+    /// round-1 state of the partner unchanged in each sampled block.
+    pub fn derive(
+        conditions: &BitConditions,
+        path: &DifferentialPath,
+        iv: [u32; 4],
+        preserve: usize,
+    ) -> TunnelSet {
+        let mut samples = round1_samples(conditions, path, iv);
+        let baseline = samples.len();
+        let mut conditions = conditions.clone();
+        let mut tunnels: Vec<Tunnel> = Vec::new();
+        let mut order: Vec<usize> = (1..=16).collect();
+        order.sort_by_key(|&i| core::cmp::Reverse(preserved_step(i, best_words(i))));
+        for i in order {
+            let row = i as isize;
+            for bit in 0..32 {
+                if tunnels.iter().map(Tunnel::strength).sum::<u32>() == MAX_STRENGTH {
+                    break;
+                }
+                let used = |q: usize| {
+                    tunnels
+                        .iter()
+                        .any(|tunnel| tunnel.q == q && tunnel.mask >> bit & 1 == 1)
+                };
+                let free = conditions.get(row, bit) == Condition::Free
+                    && !matches!(
+                        conditions.get(row + 1, bit),
+                        Condition::SameAsPrev | Condition::NotPrev
+                    )
+                    && !matches!(
+                        conditions.get(row + 2, bit),
+                        Condition::SameAsPrev2 | Condition::NotPrev2
+                    )
+                    && (row - 2..=row + 2)
+                        .filter(|&t| t != row && t <= 16)
+                        .all(|t| (path.q(t).plus | path.q(t).minus) >> bit & 1 == 0);
+                if !free {
+                    continue;
+                }
+                // Conditions that keep W_{i+1} and W_{i+2}, and whether
+                // they are already there.
+                let mut added = Vec::new();
+                let mut words = best_words(i);
+                for (offset, wanted) in [(1, Condition::Zero), (2, Condition::One)] {
+                    let current = conditions.get(row + offset as isize, bit);
+                    if i + offset >= 16 || current == wanted {
+                        continue;
+                    }
+                    if current == Condition::Free && !used(i + offset) {
+                        added.push((row + offset as isize, wanted));
+                    } else {
+                        words |= 1 << (i + offset);
+                    }
+                }
+                if preserved_step(i, words) < preserve {
+                    continue;
+                }
+                if !added.is_empty() {
+                    let mut trial = conditions.clone();
+                    for (t, condition) in added {
+                        trial.set(t, bit, condition);
+                    }
+                    let trial_samples = round1_samples(&trial, path, iv);
+                    if 2 * trial_samples.len() < baseline
+                        || !keeps_pairs(path, iv, &trial_samples, i, 1 << bit)
+                    {
+                        continue;
+                    }
+                    conditions = trial;
+                    samples = trial_samples;
+                } else if !keeps_pairs(path, iv, &samples, i, 1 << bit) {
+                    continue;
+                }
+                match tunnels
+                    .iter_mut()
+                    .find(|tunnel| tunnel.q == i && tunnel.words == words)
+                {
+                    Some(tunnel) => tunnel.mask |= 1 << bit,
+                    None => tunnels.push(Tunnel {
+                        q: i,
+                        mask: 1 << bit,
+                        words,
+                        preserves: preserved_step(i, words),
+                    }),
+                }
+            }
+        }
+        tunnels.sort_by_key(|tunnel| (core::cmp::Reverse(tunnel.preserves), tunnel.q));
+        TunnelSet {
+            conditions,
+            tunnels,
+        }
+    }
+
+    /// Total number of tunnel bits.
+    pub fn strength(&self) -> u32 {
+        self.tunnels.iter().map(Tunnel::strength).sum()
+    }
+
+    /// Call `visit` on `block` and on every other setting of its tunnel
+    /// bits, until it returns `true`.
+    ///
+    /// The settings are walked in Gray code order, so each candidate costs
+    /// a single bit flip. `block` is left at the setting `visit` accepted,
+    /// or at the last one. Returns whether `visit` accepted one.
+    ///
+    /// Panics if the set holds more than [`MAX_STRENGTH`] bits, which
+    /// [`derive`](TunnelSet::derive) never yields.
+    pub fn enumerate<F>(&self, iv: [u32; 4], block: &mut [u32; 16], mut visit: F) -> bool
+    where
+        F: FnMut(&[u32; 16]) -> bool,
+    {
+        let bits: Vec<(&Tunnel, u32)> = self
+            .tunnels
+            .iter()
+            .flat_map(|tunnel| {
+                (0..32)
+                    .filter(move |bit| tunnel.mask >> bit & 1 == 1)
+                    .map(move |bit| (tunnel, 1 << bit))
+            })
+            .collect();
+        assert!(bits.len() as u32 <= MAX_STRENGTH, "too many tunnel bits");
+        let mut q = states(iv, block);
+        if visit(block) {
+            return true;
+        }
+        for n in 1u64..1 << bits.len() {
+            let (tunnel, bit) = bits[n.trailing_zeros() as usize];
+            tunnel.apply(&mut q, block, bit);
+            if visit(block) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// The blocks, out of [`SAMPLES`] random ones, that
+/// [`follow_round1`](BitConditions::follow_round1) puts on `path`.
+fn round1_samples(conditions: &BitConditions, path: &DifferentialPath, iv: [u32; 4]) -> Vec<[u32; 16]> {
+    let rng = fastrand::Rng::with_seed(0);
+    (0..SAMPLES)
+        .filter_map(|_| {
+            let mut block = [0u32; 16];
+            block.iter_mut().for_each(|word| *word = rng.u32(..));
+            conditions.follow_round1(path, iv, &mut block).then_some(block)
+        })
+        .collect()
+}
+
+/// Whether flipping `bits` of `Q_i` in each of `samples` leaves every other
+/// state of round 1 unchanged, in both messages of the pair.
+fn keeps_pairs(path: &DifferentialPath, iv: [u32; 4], samples: &[[u32; 16]], i: usize, bits: u32) -> bool {
+    let tunnel = Tunnel {
+        q: i,
+        mask: bits,
+        words: 0,
+        preserves: 0,
+    };
+    samples.iter().all(|block| {
+        let mut q = states(iv, block);
+        let (iv_p, block_p) = path.partner(iv, block);
+        let before_p: [u32; 20] = states(iv_p, &block_p);
+        let mut flipped = *block;
+        tunnel.apply(&mut q, &mut flipped, bits);
+        let after_p: [u32; 20] = states(iv_p, &path.partner(iv, &flipped).1);
+        (4..20).all(|k| after_p[k] == before_p[k] ^ if k == i + 3 { bits } else { 0 })
+    })
+}
+
+/// Words changed by a bit of `Q_i` when `W_{i+1}` and `W_{i+2}` are kept.
+fn best_words(i: usize) -> u16 {
+    [i - 1, i, i + 3]
+        .into_iter()
+        .filter(|&t| t < 16)
+        .fold(0, |words, t| words | 1 << t)
+}
+
+/// Last `Q_t` unchanged by flipping bits of `Q_i` that change `words`.
+fn preserved_step(i: usize, words: u16) -> usize {
+    // Q_i is an input of steps i to i + 3; those in round 2 change directly.
+    let direct = if i + 3 >= 16 { 16 } else { 64 };
+    let through_words = (16..64)
+        .find(|&t| words >> WORD_INDEX[t] & 1 == 1)
+        .unwrap_or(64);
+    direct.min(through_words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts;
+    use crate::differential::Bsdr;
+    use crate::md5::{transform_traced, Context};
+    use crate::md5_attack::{wang_second_block, wang_second_block_path};
+
+    #[test]
+    fn tunnels_preserve_states() {
+        let iv = consts::published_iv();
+        let set = TunnelSet::derive(wang_second_block(), wang_second_block_path(), iv, 20);
+        assert!(set.strength() > 0);
+
+        let path = wang_second_block_path();
+        let deviations = |block: &[u32; 16], t| {
+            let (iv_p, block_p) = path.partner(iv, block);
+            let (trace, trace_p) = (transform_traced(iv, block), transform_traced(iv_p, &block_p));
+            path.deviations(t, Bsdr::between(trace.q(t), trace_p.q(t)))
+        };
+
+        let rng = fastrand::Rng::with_seed(2);
+        let mut block = [0u32; 16];
+        loop {
+            block.iter_mut().for_each(|word| *word = rng.u32(..));
+            if set.conditions.follow_round1(path, iv, &mut block) {
+                break;
+            }
+        }
+        let before = transform_traced(iv, &block);
+        for tunnel in &set.tunnels {
+            let mut q = states(iv, &block);
+            let mut flipped = block;
+            tunnel.apply(&mut q, &mut flipped, tunnel.mask);
+            let after = transform_traced(iv, &flipped);
+            for t in 1..=tunnel.preserves as isize {
+                let expected = if t == tunnel.q as isize {
+                    before.q(t) ^ tunnel.mask
+                } else {
+                    before.q(t)
+                };
+                assert_eq!(after.q(t), expected, "Q{} after flipping Q{}", t, tunnel.q);
+                // The partner keeps its difference to the first message.
+                assert_eq!(
+                    deviations(&flipped, t),
+                    deviations(&block, t),
+                    "partner Q{} after flipping Q{}",
+                    t,
+                    tunnel.q
+                );
+            }
+            for word in 0..16 {
+                if tunnel.words >> word & 1 == 0 {
+                    assert_eq!(flipped[word], block[word]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn strength_is_capped() {
+        // Without conditions nearly every bit of round 1 is a tunnel.
+        let iv = Context::new().state();
+        let path = wang_second_block_path();
+        let set = TunnelSet::derive(&BitConditions::default(), path, iv, 17);
+        assert_eq!(set.strength(), MAX_STRENGTH);
+    }
+}
@@ -11,16 +11,24 @@ pub fn str_to_bytes(s: &str) -> Vec<u8> {
     v   
 }
 
+/// A decimal number, or a hexadecimal one prefixed with `0x`.
+pub fn parse_u64(value: &str) -> Option<u64> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
 pub fn verify(m0: &Vec<u8>, m1: &Vec<u8>, m0_p: &Vec<u8>, m1_p: &Vec<u8>) -> bool {
     let mut context = md5::Context::new();
-    context.consume(m0, false);
-    context.consume(m1, false);
-    let digest = context.compute();
+    context.consume(m0);
+    context.consume(m1);
+    let digest = context.chaining_value();
 
     let mut context_p = md5::Context::new();
-    context_p.consume(m0_p, false);
-    context_p.consume(m1_p, false);
-    let digest_p = context_p.compute();
+    context_p.consume(m0_p);
+    context_p.consume(m1_p);
+    let digest_p = context_p.chaining_value();
     
     println!("m  -> {:x}", digest);
     println!("m' -> {:x}", digest_p);